@@ -1,50 +1,928 @@
-use std::ops::Sub;
+use std::ops::Range;
 
-use kurbo::{DEFAULT_ACCURACY, Line, ParamCurve, PathSeg, Shape};
+use kurbo::{
+    CubicBez, CurveFitSample, Line, ParamCurve, ParamCurveArclen, ParamCurveDeriv, ParamCurveFit,
+    ParamCurveNearest, PathSeg, Point, Rect, Shape, Vec2,
+};
 
-fn intersections(
+/// Recursion limit for Bézier clipping, reached only around tangential intersections.
+const MAX_CLIP_DEPTH: usize = 64;
+
+/// Cap on the sub-curve pairs Bézier clipping visits for one pair of segments, so curves running
+/// just outside `accuracy` of each other cannot keep it subdividing for ever.
+const MAX_CLIP_STEPS: usize = 1 << 16;
+
+/// Slack on the `0..=1` parameter range when solving line intersections analytically, so hits
+/// right at an endpoint are not lost to rounding.
+const ENDPOINT_EPSILON: f64 = 1e-9;
+
+/// Intersections of two segments as `(t1, t2, point)` triples, sorted by `t1`.
+///
+/// Lines against lines and lines against curves are solved analytically. Two curves are
+/// intersected by Bézier clipping, which narrows both parameter ranges until each sub-curve fits
+/// within `accuracy` and then polishing the hit with Newton steps, so every reported point lies
+/// within `accuracy` of both curves. Hits closer than that to each other are reported once, as is
+/// each stretch where the curves run within `accuracy` of each other, like around a tangency.
+///
+/// Segments that overlap along a stretch have infinitely many intersections, find those with
+/// [`pathseg_overlaps`] first.
+pub fn pathseg_intersections(
     seg1: PathSeg,
-    min1: f64,
-    max1: f64,
     seg2: PathSeg,
-    min2: f64,
-    max2: f64,
-    result: &mut Vec<f64>,
+    accuracy: f64,
+) -> Vec<(f64, f64, Point)> {
+    let mut result = match (seg1, seg2) {
+        (PathSeg::Line(line1), PathSeg::Line(line2)) => {
+            line_line_intersection(line1, line2).into_iter().collect()
+        }
+        (PathSeg::Line(line), curve) => line_curve_intersections(line, curve),
+        (curve, PathSeg::Line(line)) => line_curve_intersections(line, curve)
+            .into_iter()
+            .map(|(t_line, t_curve, point)| (t_curve, t_line, point))
+            .collect(),
+        (curve1, curve2) => {
+            let (a, b) = (to_cubic(curve1), to_cubic(curve2));
+            let mut clusters = Vec::new();
+            let mut budget = MAX_CLIP_STEPS;
+            clip_intersections(
+                a,
+                0.0..1.0,
+                b,
+                0.0..1.0,
+                false,
+                0,
+                accuracy,
+                &mut budget,
+                &mut clusters,
+            );
+            merge_clusters(a, b, clusters, accuracy)
+                .into_iter()
+                .map(|(a_range, b_range)| {
+                    let (ta, tb) = refine_intersection(
+                        a,
+                        b,
+                        lerp_range(&a_range, 0.5),
+                        lerp_range(&b_range, 0.5),
+                    );
+                    (ta, tb, a.eval(ta))
+                })
+                .collect()
+        }
+    };
+
+    // Degenerate segments, like a cubic with all four points together, give no direction to clip
+    // against and can come out as NaN.
+    result.retain(|(t1, t2, point)| t1.is_finite() && t2.is_finite() && point.is_finite());
+    result.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut deduped: Vec<(f64, f64, Point)> = Vec::new();
+    for hit in result {
+        if !deduped
+            .iter()
+            .any(|kept| kept.2.distance(hit.2) <= 2. * accuracy)
+        {
+            deduped.push(hit);
+        }
+    }
+    deduped
+}
+
+fn to_cubic(segment: PathSeg) -> CubicBez {
+    match segment {
+        PathSeg::Line(line) => CubicBez::new(
+            line.p0,
+            line.p0.lerp(line.p1, 1. / 3.),
+            line.p0.lerp(line.p1, 2. / 3.),
+            line.p1,
+        ),
+        PathSeg::Quad(quad) => quad.raise(),
+        PathSeg::Cubic(cubic) => cubic,
+    }
+}
+
+fn line_line_intersection(line1: Line, line2: Line) -> Option<(f64, f64, Point)> {
+    let d1 = line1.p1 - line1.p0;
+    let d2 = line2.p1 - line2.p0;
+    let det = d1.cross(d2);
+    if det.abs() <= f64::EPSILON * d1.hypot() * d2.hypot() {
+        // Parallel lines, overlaps are not reported here.
+        return None;
+    }
+
+    let w = line2.p0 - line1.p0;
+    let t1 = w.cross(d2) / det;
+    let t2 = w.cross(d1) / det;
+    let range = -ENDPOINT_EPSILON..=1. + ENDPOINT_EPSILON;
+    if !range.contains(&t1) || !range.contains(&t2) {
+        return None;
+    }
+    let t1 = t1.clamp(0., 1.);
+    Some((t1, t2.clamp(0., 1.), line1.eval(t1)))
+}
+
+/// Intersections as `(t_line, t_curve, point)`.
+fn line_curve_intersections(line: Line, curve: PathSeg) -> Vec<(f64, f64, Point)> {
+    // Extend the probe a little so hits at the very ends of the line are kept.
+    let extension = (line.p1 - line.p0) * ENDPOINT_EPSILON;
+    let probe = Line::new(line.p0 - extension, line.p1 + extension);
+    let scale = 1. + 2. * ENDPOINT_EPSILON;
+
+    curve
+        .intersect_line(probe)
+        .into_iter()
+        .map(|hit| {
+            let t_line = (hit.line_t * scale - ENDPOINT_EPSILON).clamp(0., 1.);
+            let t_curve = hit.segment_t.clamp(0., 1.);
+            (t_line, t_curve, curve.eval(t_curve))
+        })
+        .collect()
+}
+
+/// Parameter at `t` of the way through `range`.
+pub fn lerp_range(range: &Range<f64>, t: f64) -> f64 {
+    range.start + (range.end - range.start) * t
+}
+
+fn diagonal(rect: Rect) -> f64 {
+    rect.width().hypot(rect.height())
+}
+
+/// Bézier clipping of `a` against the fat line of `b` on the given parameter ranges, alternating
+/// the roles of the curves and falling back to subdivision when clipping stalls.
+///
+/// Pushes the `(a_range, b_range)` pairs, in the caller's orientation, that contain a hit. Where
+/// clipping stalls on a sub-curve that already lies within `accuracy` of the other one, like
+/// around a tangency or along a near-coincident stretch, the whole pair is pushed instead of
+/// subdividing it further. Each call spends one step of `budget` and nothing more is explored
+/// once it runs out.
+#[allow(clippy::too_many_arguments)]
+fn clip_intersections(
+    a: CubicBez,
+    a_range: Range<f64>,
+    b: CubicBez,
+    b_range: Range<f64>,
+    flipped: bool,
+    depth: usize,
+    accuracy: f64,
+    budget: &mut usize,
+    clusters: &mut Vec<(Range<f64>, Range<f64>)>,
 ) {
-    let bbox1 = seg1.subsegment(min1..max1).bounding_box();
-    let bbox2 = seg2.subsegment(min2..max2).bounding_box();
+    if *budget == 0 {
+        return;
+    }
+    *budget -= 1;
+
+    let sub_a = a.subsegment(a_range.clone());
+    let sub_b = b.subsegment(b_range.clone());
+    let bbox_a = sub_a.bounding_box();
+    let bbox_b = sub_b.bounding_box();
+    if !bbox_a
+        .inflate(accuracy, accuracy)
+        .overlaps(bbox_b.inflate(accuracy, accuracy))
+    {
+        return;
+    }
+
+    let mut push = |a_range: Range<f64>, b_range: Range<f64>| {
+        clusters.push(if flipped {
+            (b_range, a_range)
+        } else {
+            (a_range, b_range)
+        });
+    };
+
+    let a_converged = diagonal(bbox_a) < accuracy;
+    let b_converged = diagonal(bbox_b) < accuracy;
+    if (a_converged && b_converged) || depth >= MAX_CLIP_DEPTH {
+        // The boxes only overlap within twice `accuracy`, keep the pair if the curves really meet.
+        let (ta, tb) =
+            refine_intersection(a, b, lerp_range(&a_range, 0.5), lerp_range(&b_range, 0.5));
+        if a.eval(ta).distance(b.eval(tb)) <= accuracy {
+            push(a_range, b_range);
+        }
+        return;
+    }
+
+    let clipped = if b_converged {
+        // A point has no fat line, just narrow `a` down by subdivision.
+        Some((0., 1.))
+    } else {
+        fat_line_clip(sub_a, sub_b, accuracy)
+    };
+    let Some((t_min, t_max)) = clipped else {
+        return;
+    };
+    let a_range = lerp_range(&a_range, t_min)..lerp_range(&a_range, t_max);
 
-    if bbox1.overlaps(bbox2) {
-        let mid1 = (min1 + max1) / 2.;
-        let mid2 = (min2 + max2) / 2.;
+    if t_max - t_min <= 0.8 {
+        clip_intersections(
+            b,
+            b_range,
+            a,
+            a_range,
+            !flipped,
+            depth + 1,
+            accuracy,
+            budget,
+            clusters,
+        );
+        return;
+    }
 
-        if bbox1.width().abs() < DEFAULT_ACCURACY && bbox1.height().abs() < DEFAULT_ACCURACY {
-            result.push(min1);
+    // Clipping removed too little. Where the shorter sub-curve already runs along the other one,
+    // the stretch they share is one cluster, otherwise split the larger curve and keep going on
+    // both halves.
+    let sub_a = a.subsegment(a_range.clone());
+    if diagonal(bbox_a) <= diagonal(bbox_b) {
+        if within_accuracy(sub_a, sub_b, accuracy) {
+            let b_range = shared_range(sub_a, sub_b, &b_range, accuracy);
+            push(a_range, b_range);
             return;
         }
-        intersections(seg1, min1, mid1, seg2, min2, mid2, result);
-        intersections(seg1, min1, mid1, seg2, mid2, max2, result);
-        intersections(seg1, mid1, max1, seg2, min2, mid2, result);
-        intersections(seg1, mid1, max1, seg2, mid2, max2, result);
+    } else if within_accuracy(sub_b, sub_a, accuracy) {
+        let a_range = shared_range(sub_b, sub_a, &a_range, accuracy);
+        push(a_range, b_range);
+        return;
+    }
+    if diagonal(bbox_a) >= diagonal(bbox_b) {
+        let mid = lerp_range(&a_range, 0.5);
+        for half in [a_range.start..mid, mid..a_range.end] {
+            let half_b = b_range.clone();
+            clip_intersections(
+                b,
+                half_b,
+                a,
+                half,
+                !flipped,
+                depth + 1,
+                accuracy,
+                budget,
+                clusters,
+            );
+        }
+    } else {
+        let mid = lerp_range(&b_range, 0.5);
+        for half in [b_range.start..mid, mid..b_range.end] {
+            clip_intersections(
+                b,
+                half,
+                a,
+                a_range.clone(),
+                !flipped,
+                depth + 1,
+                accuracy,
+                budget,
+                clusters,
+            );
+        }
     }
 }
 
-// TODO: Cleanup and find a better way to do this
-pub fn pathseg_intersections(seg1: PathSeg, seg2: PathSeg) -> Vec<f64> {
-    let mut result = Vec::new();
-    intersections(seg1, 0., 1., seg2, 0., 1., &mut result);
-    result
+/// Whether every point of `a` lies within `accuracy` of `b`, checked at the ends and a few points
+/// in between.
+fn within_accuracy(a: CubicBez, b: CubicBez, accuracy: f64) -> bool {
+    (0..=8).all(|i| {
+        let point = a.eval(i as f64 / 8.);
+        b.nearest(point, accuracy * 1e-3).distance_sq <= accuracy * accuracy
+    })
+}
+
+/// Parameter range of `longer`, a sub-curve covering `range`, that lies alongside `shorter`.
+fn shared_range(
+    shorter: CubicBez,
+    longer: CubicBez,
+    range: &Range<f64>,
+    accuracy: f64,
+) -> Range<f64> {
+    let t0 = lerp_range(range, longer.nearest(shorter.p0, accuracy * 1e-3).t);
+    let t1 = lerp_range(range, longer.nearest(shorter.p3, accuracy * 1e-3).t);
+    t0.min(t1)..t0.max(t1)
+}
+
+/// Joins clusters whose parameter ranges touch on both curves, or whose curves stay within
+/// `accuracy` of each other across the gap, so a run of sub-curves around one tangency or
+/// coincident stretch becomes a single hit.
+fn merge_clusters(
+    a: CubicBez,
+    b: CubicBez,
+    mut clusters: Vec<(Range<f64>, Range<f64>)>,
+    accuracy: f64,
+) -> Vec<(Range<f64>, Range<f64>)> {
+    let touch = |r1: &Range<f64>, r2: &Range<f64>| {
+        r1.start <= r2.end + ENDPOINT_EPSILON && r2.start <= r1.end + ENDPOINT_EPSILON
+    };
+    let join = |r1: &Range<f64>, r2: &Range<f64>| r1.start.min(r2.start)..r1.end.max(r2.end);
+
+    clusters.sort_by(|c1, c2| c1.0.start.total_cmp(&c2.0.start));
+    loop {
+        let count = clusters.len();
+        let mut merged: Vec<(Range<f64>, Range<f64>)> = Vec::new();
+        for (a_range, b_range) in clusters {
+            let joins = |(a_kept, b_kept): &(Range<f64>, Range<f64>)| {
+                touch(a_kept, &a_range) && touch(b_kept, &b_range)
+            };
+            let across_gap = |(a_last, b_last): &(Range<f64>, Range<f64>)| {
+                within_accuracy(a.subsegment(join(a_last, &a_range)), b, accuracy)
+                    && within_accuracy(b.subsegment(join(b_last, &b_range)), a, accuracy)
+            };
+            let target = match merged.iter().position(joins) {
+                Some(i) => Some(i),
+                None => merged
+                    .last()
+                    .is_some_and(across_gap)
+                    .then(|| merged.len() - 1),
+            };
+            match target {
+                Some(i) => {
+                    let (a_kept, b_kept) = &mut merged[i];
+                    *a_kept = join(a_kept, &a_range);
+                    *b_kept = join(b_kept, &b_range);
+                }
+                None => merged.push((a_range, b_range)),
+            }
+        }
+        if merged.len() == count {
+            return merged;
+        }
+        clusters = merged;
+    }
+}
+
+/// Polishes an intersection found by clipping with a few Newton steps on `a(ta) = b(tb)`, keeping
+/// the original parameters if that does not bring the curves closer together.
+fn refine_intersection(a: CubicBez, b: CubicBez, ta: f64, tb: f64) -> (f64, f64) {
+    let (da, db) = (a.deriv(), b.deriv());
+    let residual = |ta: f64, tb: f64| a.eval(ta) - b.eval(tb);
+
+    let (mut ra, mut rb) = (ta, tb);
+    for _ in 0..8 {
+        let f = residual(ra, rb);
+        let (ja, jb) = (da.eval(ra).to_vec2(), db.eval(rb).to_vec2());
+        let det = jb.cross(ja);
+        if det.abs() < 1e-12 {
+            break;
+        }
+        // Solve `ja * dta - jb * dtb = -f` by Cramer's rule.
+        ra = (ra - jb.cross(f) / det).clamp(0., 1.);
+        rb = (rb - ja.cross(f) / det).clamp(0., 1.);
+    }
+
+    if residual(ra, rb).hypot2() < residual(ta, tb).hypot2() {
+        (ra, rb)
+    } else {
+        (ta, tb)
+    }
 }
 
-pub fn cleanup_intersections(intersections: Vec<f64>) -> Vec<f64> {
-    intersections.iter().fold(Vec::new(), |mut acc, &t| {
-        if let Some(&last_t) = acc.last() {
-            if last_t.sub(t).abs() > 0.01 {
-                acc.push(t);
+/// Parameter range of `a` that can lie inside the fat line bounding `b`, widened by `accuracy`
+/// to absorb rounding, or [`None`] if `a` misses it entirely.
+fn fat_line_clip(a: CubicBez, b: CubicBez, accuracy: f64) -> Option<(f64, f64)> {
+    let mut direction = b.p3 - b.p0;
+    for fallback in [b.p2 - b.p0, b.p1 - b.p0] {
+        if direction.hypot2() > 1e-24 {
+            break;
+        }
+        direction = fallback;
+    }
+    if direction.hypot2() <= 1e-24 {
+        return Some((0., 1.));
+    }
+
+    let normal = Vec2::new(-direction.y, direction.x).normalize();
+    let distance = |p: Point| normal.dot(p - b.p0);
+
+    let d1 = distance(b.p1);
+    let d2 = distance(b.p2);
+    let factor = if d1 * d2 > 0. { 3. / 4. } else { 4. / 9. };
+    let d_min = factor * d1.min(d2).min(0.) - accuracy;
+    let d_max = factor * d1.max(d2).max(0.) + accuracy;
+
+    // Control points of the distance of `a` to the fat line, as a function of its parameter.
+    let points = [a.p0, a.p1, a.p2, a.p3]
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| (i as f64 / 3., distance(p)))
+        .collect::<Vec<(f64, f64)>>();
+
+    // The convex hull of the distance control points bounds the curve, so the clipped range
+    // spans the points inside the band and the crossings of the band limits by the hull edges.
+    let mut t_min = f64::INFINITY;
+    let mut t_max = f64::NEG_INFINITY;
+    for (i, &(ti, di)) in points.iter().enumerate() {
+        if (d_min..=d_max).contains(&di) {
+            t_min = t_min.min(ti);
+            t_max = t_max.max(ti);
+        }
+        for &(tj, dj) in &points[i + 1..] {
+            for bound in [d_min, d_max] {
+                if (di - bound) * (dj - bound) < 0. {
+                    let t = ti + (bound - di) / (dj - di) * (tj - ti);
+                    t_min = t_min.min(t);
+                    t_max = t_max.max(t);
+                }
             }
+        }
+    }
+
+    if t_min > t_max {
+        None
+    } else {
+        Some((t_min.max(0.), t_max.min(1.)))
+    }
+}
+
+/// Stretches where two segments run along each other, as pairs of parameter ranges on `seg1` and
+/// `seg2`, sorted along `seg1`. The range on `seg2` is reversed when the segments run in opposite
+/// directions.
+///
+/// Overlaps always start and end at an endpoint of one of the segments, so those are the only
+/// candidates. Consecutive candidates bound an overlap when the curve between them stays within
+/// `accuracy` of the other segment, while moving along it in a consistent direction.
+pub fn pathseg_overlaps(
+    seg1: PathSeg,
+    seg2: PathSeg,
+    accuracy: f64,
+) -> Vec<(Range<f64>, Range<f64>)> {
+    let nearest_accuracy = accuracy * 0.1;
+    let on_other = |from: PathSeg, to: PathSeg, t: f64| {
+        let nearest = to.nearest(from.eval(t), nearest_accuracy);
+        (nearest.distance_sq <= accuracy * accuracy).then_some(nearest.t)
+    };
+
+    let mut candidates = Vec::new();
+    for t1 in [0., 1.] {
+        if let Some(t2) = on_other(seg1, seg2, t1) {
+            candidates.push((t1, t2));
+        }
+    }
+    for t2 in [0., 1.] {
+        if let Some(t1) = on_other(seg2, seg1, t2) {
+            candidates.push((t1, t2));
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    candidates.dedup_by(|b, a| seg1.eval(a.0).distance(seg1.eval(b.0)) <= accuracy);
+
+    let mut overlaps: Vec<(Range<f64>, Range<f64>)> = Vec::new();
+    for pair in candidates.windows(2) {
+        let ((start1, start2), (end1, end2)) = (pair[0], pair[1]);
+        if seg2.eval(start2).distance(seg2.eval(end2)) <= accuracy {
+            continue;
+        }
+        let (low2, high2) = (start2.min(end2), start2.max(end2));
+        let shared = [0.25, 0.5, 0.75].iter().all(|&f| {
+            on_other(seg1, seg2, lerp_range(&(start1..end1), f))
+                .is_some_and(|t2| (low2..=high2).contains(&t2))
+        });
+        if !shared {
+            continue;
+        }
+        match overlaps.last_mut() {
+            Some((range1, range2)) if range1.end == start1 && range2.end == start2 => {
+                range1.end = end1;
+                range2.end = end2;
+            }
+            _ => overlaps.push((start1..end1, start2..end2)),
+        }
+    }
+    overlaps
+}
+
+/// Pairs `(i, j)` with `i < j` of boxes that overlap once inflated by `margin`, in increasing
+/// order.
+///
+/// Sweep and prune along x: boxes are visited by their left edge and only tested against the boxes
+/// still open at that point, so the cost follows the number of boxes sharing an x range rather
+/// than the number of pairs.
+pub fn overlapping_boxes(boxes: &[Rect], margin: f64) -> Vec<(usize, usize)> {
+    let mut order = (0..boxes.len()).collect::<Vec<usize>>();
+    order.sort_by(|&a, &b| boxes[a].x0.total_cmp(&boxes[b].x0).then(a.cmp(&b)));
+
+    let mut pairs = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+    for i in order {
+        let rect = boxes[i];
+        active.retain(|&j| boxes[j].x1 + margin >= rect.x0 - margin);
+        for &j in &active {
+            let other = boxes[j];
+            if other.y0 - margin <= rect.y1 + margin && rect.y0 - margin <= other.y1 + margin {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+        active.push(i);
+    }
+    pairs.sort();
+    pairs
+}
+
+/// Parameters `(s, t)` with `s < t` where a cubic crosses itself, if it does.
+///
+/// With the cubic in power form `a t³ + b t² + c t + d`, `B(s) = B(t)` for `s != t` reduces to
+/// `a (u² - v) + b u + c = 0` where `u = s + t` and `v = s t`, which is linear in `u² - v` and `u`.
+pub fn cubic_self_intersection(cubic: CubicBez) -> Option<(f64, f64)> {
+    let (p0, p1, p2, p3) = (
+        cubic.p0.to_vec2(),
+        cubic.p1.to_vec2(),
+        cubic.p2.to_vec2(),
+        cubic.p3.to_vec2(),
+    );
+    let a = -p0 + p1 * 3. - p2 * 3. + p3;
+    let b = p0 * 3. - p1 * 6. + p2 * 3.;
+    let c = (p1 - p0) * 3.;
+
+    let det = a.cross(b);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let u = c.cross(a) / det;
+    let v = u * u - b.cross(c) / det;
+
+    let discriminant = u * u - 4. * v;
+    if discriminant <= 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (s, t) = ((u - root) / 2., (u + root) / 2.);
+    let range = -ENDPOINT_EPSILON..=1. + ENDPOINT_EPSILON;
+    if range.contains(&s) && range.contains(&t) {
+        Some((s.clamp(0., 1.), t.clamp(0., 1.)))
+    } else {
+        None
+    }
+}
+
+/// Self-intersection of a segment, only cubics can cross themselves.
+pub fn pathseg_self_intersection(segment: PathSeg) -> Option<(f64, f64)> {
+    match segment {
+        PathSeg::Cubic(cubic) => cubic_self_intersection(cubic),
+        PathSeg::Line(_) | PathSeg::Quad(_) => None,
+    }
+}
+
+/// Sorts the split parameters of `segment` and drops the ones closer than `separation`, measured
+/// along the curve, to the previously kept one or to the end of the segment. The result always
+/// starts at `0` and ends at `1`.
+pub fn cleanup_intersections(
+    segment: PathSeg,
+    mut intersections: Vec<f64>,
+    separation: f64,
+) -> Vec<f64> {
+    intersections.sort_by(|a, b| a.total_cmp(b));
+
+    let accuracy = separation * 1e-3;
+    let length = |range: Range<f64>| segment.subsegment(range).arclen(accuracy);
+
+    let mut result = vec![0.];
+    let mut last_t = 0.;
+    for t in intersections {
+        if length(last_t..t) >= separation && length(t..1.) >= separation {
+            result.push(t);
+            last_t = t;
+        }
+    }
+    result.push(1.);
+    result
+}
+
+/// Consecutive segments seen as a single curve for fitting, each segment taking an equal share of
+/// the `0..1` parameter range.
+pub struct ChainCurve {
+    segments: Vec<PathSeg>,
+}
+
+impl ChainCurve {
+    /// Minimum angle (in radians) between the tangents at a joint for it to be kept as a corner.
+    const CORNER_ANGLE: f64 = 0.5;
+
+    pub fn new(segments: Vec<PathSeg>) -> Self {
+        Self { segments }
+    }
+
+    /// Index of the segment holding `t` and the parameter local to it.
+    fn locate(&self, t: f64) -> (usize, f64) {
+        let n = self.segments.len();
+        let scaled = t.clamp(0., 1.) * n as f64;
+        let index = (scaled.floor() as usize).min(n - 1);
+        (index, scaled - index as f64)
+    }
+
+    fn deriv(segment: PathSeg, t: f64) -> Vec2 {
+        let deriv = match segment {
+            PathSeg::Line(line) => line.deriv().eval(t),
+            PathSeg::Quad(quad) => quad.deriv().eval(t),
+            PathSeg::Cubic(cubic) => cubic.deriv().eval(t),
+        }
+        .to_vec2();
+
+        // Handles lying on their anchor give a zero derivative, fall back to a secant.
+        if deriv.hypot2() > 1e-18 {
+            deriv
+        } else if t < 0.5 {
+            segment.eval(t + 1e-3) - segment.eval(t)
         } else {
-            acc.push(t);
+            segment.eval(t) - segment.eval(t - 1e-3)
         }
-        acc
-    })
+    }
+}
+
+impl ParamCurveFit for ChainCurve {
+    fn sample_pt_tangent(&self, t: f64, sign: f64) -> CurveFitSample {
+        let (mut index, mut local_t) = self.locate(t);
+        if local_t == 0. && index > 0 && sign < 0. {
+            index -= 1;
+            local_t = 1.;
+        }
+        let segment = self.segments[index];
+        CurveFitSample {
+            p: segment.eval(local_t),
+            tangent: Self::deriv(segment, local_t),
+        }
+    }
+
+    fn sample_pt_deriv(&self, t: f64) -> (Point, Vec2) {
+        let (index, local_t) = self.locate(t);
+        let segment = self.segments[index];
+        (
+            segment.eval(local_t),
+            Self::deriv(segment, local_t) * self.segments.len() as f64,
+        )
+    }
+
+    fn break_cusp(&self, range: Range<f64>) -> Option<f64> {
+        let n = self.segments.len() as f64;
+        let first = (range.start * n).floor() as usize + 1;
+        let last = (range.end * n).ceil() as usize;
+
+        (first..last).map(|joint| joint as f64 / n).find(|&t| {
+            if t <= range.start || t >= range.end {
+                return false;
+            }
+            let joint = (t * n).round() as usize;
+            let incoming = Self::deriv(self.segments[joint - 1], 1.);
+            let outgoing = Self::deriv(self.segments[joint], 0.);
+            incoming.cross(outgoing).atan2(incoming.dot(outgoing)).abs() > Self::CORNER_ANGLE
+        })
+    }
+}
+
+/// Assignment of rows to columns of `cost` with the lowest total cost, each column taken by at
+/// most one row. Rows left over when there are fewer columns get [`None`].
+///
+/// Hungarian method with potentials, `O(n³)` in the larger side of the matrix.
+pub fn min_cost_assignment(cost: &[Vec<f64>]) -> Vec<Option<usize>> {
+    let rows = cost.len();
+    let columns = cost.iter().map(Vec::len).max().unwrap_or(0);
+    // Square the matrix with free dummy rows or columns, indices start at 1 so 0 can stand for
+    // the column being added to the matching.
+    let n = rows.max(columns);
+    let entry = |i: usize, j: usize| cost.get(i - 1).and_then(|row| row.get(j - 1)).copied();
+    let entry = |i: usize, j: usize| entry(i, j).unwrap_or(0.);
+
+    let mut u = vec![0.; n + 1];
+    let mut v = vec![0.; n + 1];
+    // Row matched to each column.
+    let mut matched = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+    for i in 1..=n {
+        matched[0] = i;
+        let mut j0 = 0;
+        let mut min = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = matched[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced = entry(i0, j) - u[i0] - v[j];
+                if reduced < min[j] {
+                    min[j] = reduced;
+                    way[j] = j0;
+                }
+                if min[j] < delta {
+                    delta = min[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[matched[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if matched[j0] == 0 {
+                break;
+            }
+        }
+        // Flip the augmenting path back to the root.
+        while j0 != 0 {
+            let j1 = way[j0];
+            matched[j0] = matched[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut result = vec![None; rows];
+    for j in 1..=columns {
+        if (1..=rows).contains(&matched[j]) {
+            result[matched[j] - 1] = Some(j - 1);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{DEFAULT_ACCURACY, QuadBez};
+
+    use super::*;
+
+    fn assert_on_both(seg1: PathSeg, seg2: PathSeg, hits: &[(f64, f64, Point)]) {
+        for &(t1, t2, point) in hits {
+            assert!(seg1.eval(t1).distance(point) < 1e-5);
+            assert!(seg2.eval(t2).distance(point) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn line_line() {
+        let line1 = PathSeg::Line(Line::new((0., 0.), (10., 10.)));
+        let line2 = PathSeg::Line(Line::new((0., 10.), (10., 0.)));
+        let hits = pathseg_intersections(line1, line2, DEFAULT_ACCURACY);
+        assert_eq!(hits, vec![(0.5, 0.5, Point::new(5., 5.))]);
+
+        let touching = PathSeg::Line(Line::new((10., 10.), (20., 0.)));
+        let hits = pathseg_intersections(line1, touching, DEFAULT_ACCURACY);
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].0, hits[0].1), (1., 0.));
+    }
+
+    #[test]
+    fn curve_curve() {
+        let s_curve = PathSeg::Cubic(CubicBez::new(
+            (0., 0.),
+            (30., 100.),
+            (70., -100.),
+            (100., 0.),
+        ));
+        let mirrored = PathSeg::Cubic(CubicBez::new(
+            (0., 0.),
+            (30., -100.),
+            (70., 100.),
+            (100., 0.),
+        ));
+        let hits = pathseg_intersections(s_curve, mirrored, DEFAULT_ACCURACY);
+        assert_eq!(hits.len(), 3);
+        assert_on_both(s_curve, mirrored, &hits);
+        assert!((hits[1].0 - 0.5).abs() < 1e-6 && (hits[1].1 - 0.5).abs() < 1e-6);
+
+        let quad = PathSeg::Quad(QuadBez::new((0., 50.), (50., -50.), (100., 50.)));
+        let hits = pathseg_intersections(s_curve, quad, DEFAULT_ACCURACY);
+        assert_eq!(hits.len(), 2);
+        assert_on_both(s_curve, quad, &hits);
+
+        let line = PathSeg::Line(Line::new((0., 10.), (100., 10.)));
+        let hits = pathseg_intersections(line, s_curve, DEFAULT_ACCURACY);
+        assert_eq!(hits.len(), 2);
+        assert_on_both(line, s_curve, &hits);
+    }
+
+    #[test]
+    fn tangential_and_near_coincident_curves() {
+        let arch = CubicBez::new((0., 0.), (30., 100.), (70., 100.), (100., 0.));
+        let mirrored = CubicBez::new((0., 150.), (30., 50.), (70., 50.), (100., 150.));
+        let shifted = CubicBez::new((0., 0.), (30., 100.1), (70., 100.), (100., 0.));
+        let offset = Vec2::new(3e-6, 0.);
+        let copy = CubicBez::new(
+            arch.p0 + offset,
+            arch.p1 + offset,
+            arch.p2 + offset,
+            arch.p3 + offset,
+        );
+        let same_handle = CubicBez::new((0., 0.), (30., 100.), (-20., 120.), (-50., 60.));
+
+        // Touching at the apex, a single hit rather than a cloud around it.
+        let hits = pathseg_intersections(
+            PathSeg::Cubic(arch),
+            PathSeg::Cubic(mirrored),
+            DEFAULT_ACCURACY,
+        );
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].2.distance(Point::new(50., 75.)) < 1e-2);
+
+        // Curves running within `accuracy` of each other near their hits, each run reported once.
+        for (other, expected) in [(shifted, 2), (copy, 1), (same_handle, 1)] {
+            let (seg1, seg2) = (PathSeg::Cubic(arch), PathSeg::Cubic(other));
+            let hits = pathseg_intersections(seg1, seg2, DEFAULT_ACCURACY);
+            assert_eq!(hits.len(), expected);
+            assert_on_both(seg1, seg2, &hits);
+        }
+    }
+
+    #[test]
+    fn degenerate_segments() {
+        let point = PathSeg::Cubic(CubicBez::new((50., 0.), (50., 0.), (50., 0.), (50., 0.)));
+        let curve = PathSeg::Cubic(CubicBez::new(
+            (0., -50.),
+            (30., 50.),
+            (70., 50.),
+            (100., -50.),
+        ));
+        let line = PathSeg::Line(Line::new((50., 0.), (50., 0.)));
+        for other in [curve, line] {
+            let hits = pathseg_intersections(point, other, DEFAULT_ACCURACY);
+            assert!(
+                hits.iter()
+                    .all(|hit| hit.0.is_finite() && hit.1.is_finite())
+            );
+            let hits = pathseg_intersections(other, point, DEFAULT_ACCURACY);
+            assert!(
+                hits.iter()
+                    .all(|hit| hit.0.is_finite() && hit.1.is_finite())
+            );
+        }
+    }
+
+    #[test]
+    fn cubic_loop() {
+        let looping = CubicBez::new((0., 0.), (150., 100.), (-50., 100.), (100., 0.));
+        let (s, t) = cubic_self_intersection(looping).unwrap();
+        assert!(s < t);
+        assert!(looping.eval(s).distance(looping.eval(t)) < 1e-9);
+
+        let arch = CubicBez::new((0., 0.), (30., 100.), (70., 100.), (100., 0.));
+        assert_eq!(cubic_self_intersection(arch), None);
+    }
+
+    #[test]
+    fn sweep_matches_all_pairs() {
+        // Small deterministic generator so the boxes are scattered but the test is repeatable.
+        let mut state = 12345u64;
+        let mut random = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as f64 / (1u64 << 31) as f64
+        };
+        let boxes = (0..200)
+            .map(|_| {
+                let (x, y) = (random() * 1000., random() * 1000.);
+                Rect::new(x, y, x + random() * 60., y + random() * 60.)
+            })
+            .collect::<Vec<Rect>>();
+
+        let mut expected = Vec::new();
+        for i in 0..boxes.len() {
+            for j in i + 1..boxes.len() {
+                if boxes[i].inflate(1., 1.).overlaps(boxes[j].inflate(1., 1.)) {
+                    expected.push((i, j));
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(overlapping_boxes(&boxes, 1.), expected);
+    }
+
+    #[test]
+    fn assignment_is_optimal() {
+        // Greedily taking the cheapest entry, 1, forces the 9 and costs more than 2 + 3.
+        let cost = vec![vec![1., 2.], vec![3., 9.]];
+        assert_eq!(min_cost_assignment(&cost), vec![Some(1), Some(0)]);
+
+        let wide = vec![vec![5., 1., 4.]];
+        assert_eq!(min_cost_assignment(&wide), vec![Some(1)]);
+        let tall = vec![vec![5.], vec![1.], vec![4.]];
+        assert_eq!(min_cost_assignment(&tall), vec![None, Some(0), None]);
+        assert!(min_cost_assignment(&[]).is_empty());
+    }
+
+    #[test]
+    fn overlaps() {
+        let line1 = PathSeg::Line(Line::new((0., 0.), (100., 50.)));
+        let line2 = PathSeg::Line(Line::new((150., 75.), (50., 25.)));
+        let overlaps = pathseg_overlaps(line1, line2, 1e-6);
+        assert_eq!(overlaps.len(), 1);
+        let (range1, range2) = overlaps[0].clone();
+        assert!((range1.start - 0.5).abs() < 1e-9 && (range1.end - 1.).abs() < 1e-9);
+        assert!((range2.start - 1.).abs() < 1e-9 && (range2.end - 0.5).abs() < 1e-9);
+
+        let parallel = PathSeg::Line(Line::new((0., 10.), (100., 60.)));
+        assert!(pathseg_overlaps(line1, parallel, 1e-6).is_empty());
+
+        let cubic = CubicBez::new((0., 0.), (30., 100.), (70., 100.), (100., 0.));
+        let reversed = PathSeg::Cubic(cubic.subsegment(0.2..0.9)).reverse();
+        let overlaps = pathseg_overlaps(PathSeg::Cubic(cubic), reversed, 1e-6);
+        assert_eq!(overlaps.len(), 1);
+        let (range1, range2) = overlaps[0].clone();
+        assert!((range1.start - 0.2).abs() < 1e-6 && (range1.end - 0.9).abs() < 1e-6);
+        assert!((range2.start - 1.).abs() < 1e-6 && range2.end.abs() < 1e-6);
+
+        let crossing = PathSeg::Line(Line::new((0., 50.), (100., 50.)));
+        assert!(pathseg_overlaps(PathSeg::Cubic(cubic), crossing, 1e-6).is_empty());
+    }
 }
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use kurbo::Point;
+
+use crate::{
+    dynamic::{DynamicRegions, intersection},
+    mesh::{MMesh, PointId},
+    tolerance::Tolerances,
+};
+
+/// How a point moves from one keyframe to the next.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Stays at the keyframe until the next one is reached.
+    Hold,
+}
+
+impl Easing {
+    /// Share of the way to the next keyframe after `t` of the time between them.
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1. - (1. - t) * (1. - t),
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+            Easing::Hold => 0.,
+        }
+    }
+}
+
+/// Keyframes of one point, sorted by time. The easing of a keyframe applies on the way to the
+/// next one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    pub time: Vec<f64>,
+    pub position: Vec<Point>,
+    pub easing: Vec<Easing>,
+}
+
+impl Track {
+    pub fn new() -> Self {
+        Self {
+            time: Vec::new(),
+            position: Vec::new(),
+            easing: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    /// Adds a keyframe at `time`, replacing the one already there.
+    pub fn insert(&mut self, time: f64, position: Point, easing: Easing) {
+        let index = self.time.partition_point(|&t| t < time);
+        if self.time.get(index) == Some(&time) {
+            self.position[index] = position;
+            self.easing[index] = easing;
+            return;
+        }
+        self.time.insert(index, time);
+        self.position.insert(index, position);
+        self.easing.insert(index, easing);
+    }
+
+    pub fn remove(&mut self, time: f64) {
+        if let Some(index) = self.time.iter().position(|&t| t == time) {
+            self.time.remove(index);
+            self.position.remove(index);
+            self.easing.remove(index);
+        }
+    }
+
+    /// Position at `time`, holding the first and last keyframes outside of the track.
+    pub fn position_at(&self, time: f64) -> Option<Point> {
+        let next = self.time.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.position.first().copied();
+        }
+        if next == self.len() {
+            return self.position.last().copied();
+        }
+        let prev = next - 1;
+        let t = (time - self.time[prev]) / (self.time[next] - self.time[prev]);
+        let t = self.easing[prev].apply(t);
+        Some(self.position[prev].lerp(self.position[next], t))
+    }
+}
+
+/// Keyframed positions of the points of a mesh. Points without a track keep the position they
+/// have in the mesh being sampled.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Animation {
+    tracks: HashMap<PointId, Track>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self {
+            tracks: HashMap::new(),
+        }
+    }
+
+    pub fn track(&self, point: PointId) -> Option<&Track> {
+        self.tracks.get(&point)
+    }
+
+    pub fn insert_keyframe(&mut self, point: PointId, time: f64, position: Point, easing: Easing) {
+        self.tracks
+            .entry(point)
+            .or_default()
+            .insert(time, position, easing);
+    }
+
+    /// Keyframes every point of `mesh` where it currently is.
+    pub fn insert_mesh_keyframe(&mut self, mesh: &MMesh, time: f64, easing: Easing) {
+        for point in mesh.points_data().values() {
+            self.insert_keyframe(point.id, time, point.position, easing);
+        }
+    }
+
+    pub fn remove_keyframe(&mut self, point: PointId, time: f64) {
+        if let Some(track) = self.tracks.get_mut(&point) {
+            track.remove(time);
+            if track.is_empty() {
+                self.tracks.remove(&point);
+            }
+        }
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f64 {
+        self.tracks
+            .values()
+            .filter_map(|track| track.time.last().copied())
+            .fold(0., f64::max)
+    }
+
+    pub fn position_at(&self, point: PointId, time: f64) -> Option<Point> {
+        self.tracks.get(&point)?.position_at(time)
+    }
+
+    /// Copy of `mesh` with every keyframed point moved to where it is at `time`.
+    pub fn sample(&self, mesh: &MMesh, time: f64) -> MMesh {
+        let mut frame = mesh.clone();
+        for (&point, track) in &self.tracks {
+            if let Some(position) = track.position_at(time) {
+                frame.set_point(point, position);
+            }
+        }
+        frame
+    }
+
+    /// Regions of `mesh` sampled at each of `times`, every frame taking its styles from the one
+    /// before and the first from `regions`.
+    ///
+    /// Sampling only moves points, so the boundary structures stay the same between frames as
+    /// long as the strokes cross each other the same way.
+    pub fn frames(
+        &self,
+        mesh: &MMesh,
+        regions: DynamicRegions,
+        times: &[f64],
+        tolerances: &Tolerances,
+    ) -> Vec<DynamicRegions> {
+        let mut prev = regions;
+        let mut frames = Vec::with_capacity(times.len());
+        for &time in times {
+            let frame = self.sample(mesh, time);
+            let regions = DynamicRegions::build(intersection(&frame, tolerances), tolerances)
+                .filter_outer_regions()
+                .style(prev, frame.lineage(), tolerances);
+            prev = regions.clone();
+            frames.push(regions);
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Shape;
+
+    use super::*;
+    use crate::{color::RED, mesh::fixtures::square_with_diagonal, style::Style};
+
+    #[test]
+    fn eased_positions() {
+        let mut track = Track::new();
+        track.insert(2., Point::new(100., 0.), Easing::Linear);
+        track.insert(0., Point::new(0., 0.), Easing::EaseIn);
+        assert_eq!(track.position_at(-1.), Some(Point::new(0., 0.)));
+        assert_eq!(track.position_at(1.), Some(Point::new(25., 0.)));
+        assert_eq!(track.position_at(3.), Some(Point::new(100., 0.)));
+
+        track.insert(0., Point::new(0., 0.), Easing::EaseInOut);
+        assert_eq!(track.len(), 2);
+        assert_eq!(track.position_at(1.), Some(Point::new(50., 0.)));
+    }
+
+    #[test]
+    fn styles_follow_the_animated_shape() {
+        let (mesh, corners) = square_with_diagonal(100.);
+
+        let mut animation = Animation::new();
+        animation.insert_mesh_keyframe(&mesh, 0., Easing::Linear);
+        animation.insert_keyframe(corners[1], 1., Point::new(300., -50.), Easing::Linear);
+        assert_eq!(animation.duration(), 1.);
+
+        let tolerances = Tolerances::default();
+        let mut regions = DynamicRegions::build(intersection(&mesh, &tolerances), &tolerances)
+            .filter_outer_regions();
+        regions.apply_style(Some(Style::solid(RED)), Point::new(70., 30.));
+
+        let frames = animation.frames(&mesh, regions, &[0., 0.5, 1.], &tolerances);
+        let areas = frames
+            .iter()
+            .map(|frame| {
+                let styled = (0..frame.len())
+                    .filter(|&i| frame.region_style(i).is_some())
+                    .collect::<Vec<_>>();
+                assert_eq!(styled.len(), 1);
+                frame.region_path(styled[0]).area().abs().round()
+            })
+            .collect::<Vec<f64>>();
+        // The styled triangle is the one growing towards the moving corner.
+        assert!(areas[0] < areas[1] && areas[1] < areas[2]);
+    }
+}
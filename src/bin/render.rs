@@ -0,0 +1,154 @@
+//! Renders SVG path data through the mesh and region pipeline into a PNG without opening a window.
+//!
+//! Usage: `render [--size WxH] [--stroke WIDTH] [--gap WIDTH] [--fill X,Y,RRGGBB[,RRGGBB]]...
+//! [--hatch X,Y,RRGGBB]... OUTPUT.png PATH_DATA...`
+//!
+//! A fill with two colours is a gradient from the left of the region to its right.
+
+use std::{env, process::ExitCode};
+
+use kurbo::{BezPath, Point};
+use mesh::{
+    HEIGHT, WIDTH,
+    color::{BLACK, Color, WHITE},
+    dynamic::{DynamicRegions, intersection},
+    mesh::MMesh,
+    outline::StrokeStyle,
+    raster::Canvas,
+    style::{Hatch, Style},
+    tolerance::Tolerances,
+};
+
+/// Spacing and width of the lines of `--hatch` fills, in pixels.
+const HATCH_SPACING: f64 = 8.;
+const HATCH_WIDTH: f64 = 2.;
+
+struct Options {
+    width: usize,
+    height: usize,
+    stroke: f64,
+    gap: f64,
+    fills: Vec<(Point, Style)>,
+    output: String,
+    paths: Vec<BezPath>,
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let hex = u32::from_str_radix(value.trim_start_matches('#'), 16).ok()?;
+    Some(Color::from_hex(hex))
+}
+
+fn parse_fill(value: &str) -> Option<(Point, Style)> {
+    let mut parts = value.split(',');
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    let from = parse_color(parts.next()?)?;
+    let style = match parts.next() {
+        Some(to) => Style::horizontal_gradient(from, parse_color(to)?),
+        None => Style::solid(from),
+    };
+    Some((Point::new(x, y), style))
+}
+
+fn parse_hatch(value: &str) -> Option<(Point, Style)> {
+    let mut parts = value.split(',');
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    let color = parse_color(parts.next()?)?;
+    let hatch = Hatch::new(
+        color,
+        std::f64::consts::FRAC_PI_4,
+        HATCH_SPACING,
+        HATCH_WIDTH,
+    );
+    Some((Point::new(x, y), Style::hatch(hatch)))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        width: WIDTH as usize,
+        height: HEIGHT as usize,
+        stroke: 2.,
+        gap: 0.,
+        fills: Vec::new(),
+        output: String::new(),
+        paths: Vec::new(),
+    };
+
+    let mut args = env::args().skip(1);
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--size" => {
+                let size = value()?;
+                let (width, height) = size.split_once('x').ok_or("size must be WxH")?;
+                options.width = width.parse().map_err(|_| "invalid width")?;
+                options.height = height.parse().map_err(|_| "invalid height")?;
+            }
+            "--stroke" => {
+                options.stroke = value()?.parse().map_err(|_| "invalid stroke width")?;
+            }
+            "--gap" => {
+                options.gap = value()?.parse().map_err(|_| "invalid gap width")?;
+            }
+            "--fill" => {
+                let fill = value()?;
+                options
+                    .fills
+                    .push(parse_fill(&fill).ok_or(format!("invalid fill {fill}"))?);
+            }
+            "--hatch" => {
+                let hatch = value()?;
+                options
+                    .fills
+                    .push(parse_hatch(&hatch).ok_or(format!("invalid hatch {hatch}"))?);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    options.output = positional.next().ok_or("missing output file")?;
+    for data in positional {
+        let path = BezPath::from_svg(&data).map_err(|err| format!("{data}: {err}"))?;
+        options.paths.push(path);
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}");
+            eprintln!(
+                "usage: render [--size WxH] [--stroke WIDTH] [--gap WIDTH] [--fill X,Y,RRGGBB[,RRGGBB]]... [--hatch X,Y,RRGGBB]... OUTPUT.png PATH_DATA..."
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut mesh = MMesh::empty();
+    for path in &options.paths {
+        mesh.append_bezpath(path);
+    }
+
+    let tolerances = Tolerances::default().with_gap(options.gap);
+    let mut canvas = Canvas::new(options.width, options.height, WHITE);
+    if !options.fills.is_empty() {
+        let mut regions = DynamicRegions::build(intersection(&mesh, &tolerances), &tolerances)
+            .filter_outer_regions();
+        for (position, style) in &options.fills {
+            regions.apply_style(Some(style.clone()), *position);
+        }
+        regions.rasterize(&mut canvas);
+    }
+    canvas.stroke_mesh(&mesh, &StrokeStyle::new(options.stroke), BLACK);
+
+    if let Err(err) = canvas.write_png(&options.output) {
+        eprintln!("error: {}: {err}", options.output);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,69 @@
+/// A color represented by 4 floats: red, green, blue and alpha.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn from_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::new(
+            r as f32 / 255.,
+            g as f32 / 255.,
+            b as f32 / 255.,
+            a as f32 / 255.,
+        )
+    }
+
+    /// Opaque color from a `0xRRGGBB` value.
+    pub const fn from_hex(hex: u32) -> Self {
+        let bytes = hex.to_be_bytes();
+        Self::from_rgba(bytes[1], bytes[2], bytes[3], 255)
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Color `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        Self::new(
+            self.r * (1. - t) + other.r * t,
+            self.g * (1. - t) + other.g * t,
+            self.b * (1. - t) + other.b * t,
+            self.a * (1. - t) + other.a * t,
+        )
+    }
+}
+
+// Same values as the macroquad palette so the editor looks the same either way.
+pub const BLACK: Color = Color::new(0.00, 0.00, 0.00, 1.00);
+pub const WHITE: Color = Color::new(1.00, 1.00, 1.00, 1.00);
+pub const BLANK: Color = Color::new(0.00, 0.00, 0.00, 0.00);
+pub const GRAY: Color = Color::new(0.51, 0.51, 0.51, 1.00);
+pub const RED: Color = Color::new(0.90, 0.16, 0.22, 1.00);
+pub const GREEN: Color = Color::new(0.00, 0.89, 0.19, 1.00);
+pub const BLUE: Color = Color::new(0.00, 0.47, 0.95, 1.00);
+pub const SKYBLUE: Color = Color::new(0.40, 0.75, 1.00, 1.00);
+pub const YELLOW: Color = Color::new(0.99, 0.98, 0.00, 1.00);
+
+#[cfg(feature = "macroquad")]
+impl From<Color> for macroquad::color::Color {
+    fn from(color: Color) -> Self {
+        macroquad::color::Color::new(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(feature = "macroquad")]
+impl From<macroquad::color::Color> for Color {
+    fn from(color: macroquad::color::Color) -> Self {
+        Color::new(color.r, color.g, color.b, color.a)
+    }
+}
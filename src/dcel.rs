@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+
+use std::f64::consts::{PI, TAU};
+
+use kurbo::{BezPath, ParamCurve, PathSeg, Point, Shape, Vec2};
+
+use crate::{
+    dynamic::{Flow, IntersectData},
+    tolerance::Tolerances,
+};
+
+/// Departure angles closer than this are taken to be the same direction, so that curves leaving
+/// collinear are ordered by how they bend rather than by rounding noise.
+const ANGLE_EPSILON: f64 = 1e-9;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VertexId(usize);
+
+impl VertexId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HalfEdgeId(usize);
+
+impl HalfEdgeId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FaceId(usize);
+
+impl FaceId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// Half-edge (doubly connected edge list) view of the planar mesh made by [`IntersectData`].
+///
+/// Every sub-segment `i` gives two half-edges: `2 * i` runs along it from start to end and
+/// `2 * i + 1` runs back, so the twin of a half-edge is found by flipping the lowest bit.
+/// Faces are traversed with the face on the left of every half-edge, which makes bounded faces
+/// positively oriented and the outer face of each connected component negatively oriented.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dcel {
+    /// Position of each vertex.
+    vertices: Vec<Point>,
+    /// Half-edges leaving each vertex, sorted by increasing angle.
+    outgoing: Vec<Vec<HalfEdgeId>>,
+    /// Sub-segment curves the half-edges run along.
+    segments: Vec<PathSeg>,
+    /// Connected component of each vertex, numbered in order of their first vertex.
+    components: Vec<usize>,
+
+    origin: Vec<VertexId>,
+    next: Vec<HalfEdgeId>,
+    prev: Vec<HalfEdgeId>,
+    /// Face on the left of each half-edge, [`None`] for the degenerate faces that enclose no area.
+    face: Vec<Option<FaceId>>,
+
+    /// One half-edge on the boundary of each face.
+    faces: Vec<HalfEdgeId>,
+}
+
+impl Dcel {
+    pub fn build(intersect_data: &IntersectData, tolerances: &Tolerances) -> Self {
+        let segments = intersect_data.segments.clone();
+        let half_edges = segments.len() * 2;
+
+        let mut vertices = Vec::new();
+        let mut grid = HashMap::<(i64, i64), Vec<VertexId>>::new();
+        let mut origin = Vec::with_capacity(half_edges);
+        for segment in &segments {
+            for point in [segment.start(), segment.end()] {
+                origin.push(Self::snap_vertex(
+                    &mut vertices,
+                    &mut grid,
+                    point,
+                    tolerances.separation,
+                ));
+            }
+        }
+
+        let mut outgoing = vec![Vec::new(); vertices.len()];
+        for (index, vertex) in origin.iter().enumerate() {
+            outgoing[vertex.0].push(HalfEdgeId(index));
+        }
+
+        let curve = |e: HalfEdgeId| Self::oriented(&segments, e);
+        for edges in &mut outgoing {
+            let mut keyed = edges
+                .iter()
+                .map(|&e| {
+                    (
+                        Self::departure_angles(curve(e), tolerances.tangent_sample),
+                        e,
+                    )
+                })
+                .collect::<Vec<_>>();
+            keyed.sort_by(|((a, ab), ae), ((b, bb), be)| {
+                a.cmp(b).then(ab.total_cmp(bb)).then(ae.cmp(be))
+            });
+            *edges = keyed.into_iter().map(|(_, e)| e).collect();
+        }
+
+        let mut position = vec![0; half_edges];
+        for edges in &outgoing {
+            for (i, e) in edges.iter().enumerate() {
+                position[e.0] = i;
+            }
+        }
+
+        // Arriving at a vertex along `e`, the face on the left continues along the outgoing
+        // half-edge right before `twin(e)` in angular order.
+        let next = (0..half_edges)
+            .map(|e| {
+                let twin = e ^ 1;
+                let around = &outgoing[origin[twin].0];
+                around[(position[twin] + around.len() - 1) % around.len()]
+            })
+            .collect::<Vec<HalfEdgeId>>();
+        let mut prev = vec![HalfEdgeId(0); half_edges];
+        for (e, n) in next.iter().enumerate() {
+            prev[n.0] = HalfEdgeId(e);
+        }
+
+        let components = Self::connected_components(vertices.len(), &origin);
+
+        // A stroke with a loose end is walked along one side and back along the other, so a
+        // component made only of such strokes gives a face with no area, which is dropped.
+        let min_area = tolerances.accuracy * tolerances.separation;
+        let mut visited = vec![false; half_edges];
+        let mut face = vec![None; half_edges];
+        let mut faces = Vec::new();
+        for start in 0..half_edges {
+            if visited[start] {
+                continue;
+            }
+            let mut boundary = Vec::new();
+            let mut e = start;
+            while !visited[e] {
+                visited[e] = true;
+                boundary.push(e);
+                e = next[e].0;
+            }
+            let area =
+                Self::boundary_path(&segments, boundary.iter().map(|&e| HalfEdgeId(e))).area();
+            if area.abs() < min_area {
+                continue;
+            }
+            let id = FaceId(faces.len());
+            faces.push(HalfEdgeId(start));
+            for e in boundary {
+                face[e] = Some(id);
+            }
+        }
+
+        Self {
+            vertices,
+            outgoing,
+            segments,
+            components,
+            origin,
+            next,
+            prev,
+            face,
+            faces,
+        }
+    }
+
+    /// Finds a vertex within `separation` of `point` or adds a new one.
+    fn snap_vertex(
+        vertices: &mut Vec<Point>,
+        grid: &mut HashMap<(i64, i64), Vec<VertexId>>,
+        point: Point,
+        separation: f64,
+    ) -> VertexId {
+        let cell = |v: f64| (v / separation).floor() as i64;
+        let (cx, cy) = (cell(point.x), cell(point.y));
+
+        let mut closest: Option<(f64, VertexId)> = None;
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                for &vertex in grid.get(&(x, y)).into_iter().flatten() {
+                    let distance = vertices[vertex.0].distance(point);
+                    if distance < separation && closest.is_none_or(|(d, _)| distance < d) {
+                        closest = Some((distance, vertex));
+                    }
+                }
+            }
+        }
+        if let Some((_, vertex)) = closest {
+            return vertex;
+        }
+
+        let vertex = VertexId(vertices.len());
+        vertices.push(point);
+        grid.entry((cx, cy)).or_default().push(vertex);
+        vertex
+    }
+
+    /// Labels the vertices joined by half-edges, twins sit next to each other in `origin`.
+    fn connected_components(vertices: usize, origin: &[VertexId]) -> Vec<usize> {
+        let mut root = (0..vertices).collect::<Vec<usize>>();
+        fn find(root: &mut [usize], mut v: usize) -> usize {
+            while root[v] != v {
+                root[v] = root[root[v]];
+                v = root[v];
+            }
+            v
+        }
+        for edge in origin.chunks(2) {
+            let (a, b) = (find(&mut root, edge[0].0), find(&mut root, edge[1].0));
+            root[a.max(b)] = a.min(b);
+        }
+
+        let mut label = vec![usize::MAX; vertices];
+        let mut components = Vec::with_capacity(vertices);
+        let mut count = 0;
+        for v in 0..vertices {
+            let r = find(&mut root, v);
+            if label[r] == usize::MAX {
+                label[r] = count;
+                count += 1;
+            }
+            components.push(label[r]);
+        }
+        components
+    }
+
+    fn oriented(segments: &[PathSeg], e: HalfEdgeId) -> PathSeg {
+        let segment = segments[e.0 / 2];
+        if e.0.is_multiple_of(2) {
+            segment
+        } else {
+            segment.reverse()
+        }
+    }
+
+    /// Angle a curve leaves its start point at, in steps of [`ANGLE_EPSILON`], with the angle to
+    /// its midpoint relative to it to separate curves that leave tangent to each other.
+    fn departure_angles(curve: PathSeg, tangent_sample: f64) -> (i64, f64) {
+        let start = curve.start();
+        Self::departure_key(curve.eval(tangent_sample) - start, curve.eval(0.5) - start)
+    }
+
+    fn departure_key(tangent: Vec2, chord: Vec2) -> (i64, f64) {
+        let mut angle = tangent.atan2();
+        // `atan2` gives both -π and π for the direction of the negative x axis.
+        if angle < ANGLE_EPSILON - PI {
+            angle += TAU;
+        }
+        let bend = (chord.atan2() - angle + PI).rem_euclid(TAU) - PI;
+        ((angle / ANGLE_EPSILON).round() as i64, bend)
+    }
+
+    fn boundary_path(segments: &[PathSeg], boundary: impl Iterator<Item = HalfEdgeId>) -> BezPath {
+        let mut path = BezPath::new();
+        for e in boundary {
+            let curve = Self::oriented(segments, e);
+            if path.elements().is_empty() {
+                path.move_to(curve.start());
+            }
+            path.push(curve.as_path_el());
+        }
+        path.close_path();
+        path
+    }
+
+    pub fn vertices_len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn half_edges_len(&self) -> usize {
+        self.origin.len()
+    }
+
+    pub fn faces_len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn components_len(&self) -> usize {
+        self.components.iter().max().map_or(0, |&last| last + 1)
+    }
+
+    pub fn faces(&self) -> impl Iterator<Item = FaceId> {
+        (0..self.faces.len()).map(FaceId)
+    }
+
+    pub fn vertex(&self, vertex: VertexId) -> Point {
+        self.vertices[vertex.0]
+    }
+
+    /// Half-edges leaving `vertex` in increasing angular order.
+    pub fn outgoing(&self, vertex: VertexId) -> &[HalfEdgeId] {
+        &self.outgoing[vertex.0]
+    }
+
+    /// Connected component `vertex` belongs to.
+    pub fn component(&self, vertex: VertexId) -> usize {
+        self.components[vertex.0]
+    }
+
+    /// Connected component the boundary of `face` belongs to.
+    pub fn face_component(&self, face: FaceId) -> usize {
+        self.component(self.origin(self.faces[face.0]))
+    }
+
+    pub fn origin(&self, e: HalfEdgeId) -> VertexId {
+        self.origin[e.0]
+    }
+
+    pub fn destination(&self, e: HalfEdgeId) -> VertexId {
+        self.origin[e.0 ^ 1]
+    }
+
+    pub fn twin(&self, e: HalfEdgeId) -> HalfEdgeId {
+        HalfEdgeId(e.0 ^ 1)
+    }
+
+    pub fn next(&self, e: HalfEdgeId) -> HalfEdgeId {
+        self.next[e.0]
+    }
+
+    pub fn prev(&self, e: HalfEdgeId) -> HalfEdgeId {
+        self.prev[e.0]
+    }
+
+    /// Face on the left of `e`, [`None`] when that side of it encloses no area.
+    pub fn face(&self, e: HalfEdgeId) -> Option<FaceId> {
+        self.face[e.0]
+    }
+
+    /// Index of the sub-segment in the [`IntersectData`] the half-edge runs along.
+    pub fn segment(&self, e: HalfEdgeId) -> usize {
+        e.0 / 2
+    }
+
+    /// Vertices at the start and end of sub-segment `segment`.
+    pub fn segment_vertices(&self, segment: usize) -> (VertexId, VertexId) {
+        (self.origin[2 * segment], self.origin[2 * segment + 1])
+    }
+
+    pub fn flow(&self, e: HalfEdgeId) -> Flow {
+        if e.0.is_multiple_of(2) {
+            Flow::StartToEnd
+        } else {
+            Flow::EndToStart
+        }
+    }
+
+    /// Sub-segment curve oriented along the half-edge.
+    pub fn curve(&self, e: HalfEdgeId) -> PathSeg {
+        Self::oriented(&self.segments, e)
+    }
+
+    /// Half-edges around `face`, in order.
+    pub fn face_boundary(&self, face: FaceId) -> Vec<HalfEdgeId> {
+        let start = self.faces[face.0];
+        let mut boundary = vec![start];
+        let mut e = self.next(start);
+        while e != start {
+            boundary.push(e);
+            e = self.next(e);
+        }
+        boundary
+    }
+
+    /// Sub-segment index and direction of every half-edge around `face`.
+    pub fn face_segments(&self, face: FaceId) -> Vec<(usize, Flow)> {
+        self.face_boundary(face)
+            .into_iter()
+            .map(|e| (self.segment(e), self.flow(e)))
+            .collect()
+    }
+
+    pub fn face_path(&self, face: FaceId) -> BezPath {
+        Self::boundary_path(&self.segments, self.face_boundary(face).into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dynamic::intersection,
+        mesh::{MMesh, fixtures},
+    };
+
+    #[test]
+    fn square_with_diagonal() {
+        let (mesh, _) = fixtures::square_with_diagonal(100.);
+
+        let tolerances = Tolerances::default();
+        let dcel = Dcel::build(&intersection(&mesh, &tolerances), &tolerances);
+        assert_eq!(dcel.vertices_len(), 4);
+        assert_eq!(dcel.half_edges_len(), 10);
+        assert_eq!(dcel.faces_len(), 3);
+
+        for e in (0..dcel.half_edges_len()).map(HalfEdgeId) {
+            assert_eq!(dcel.prev(dcel.next(e)), e);
+            assert_eq!(dcel.twin(dcel.twin(e)), e);
+            assert_eq!(dcel.destination(e), dcel.origin(dcel.next(e)));
+        }
+
+        let mut areas = dcel
+            .faces()
+            .map(|face| dcel.face_path(face).area().round())
+            .collect::<Vec<f64>>();
+        areas.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(areas, vec![-10000., 5000., 5000.]);
+    }
+
+    #[test]
+    fn loose_strokes_enclose_no_face() {
+        let mut mesh = MMesh::empty();
+        let points =
+            [(0., 0.), (100., 0.), (50., 50.)].map(|(x, y)| mesh.append_point(Point::new(x, y)));
+        mesh.append_segment(points[0], None, None, points[1]);
+        mesh.append_segment(points[1], None, None, points[2]);
+
+        let tolerances = Tolerances::default();
+        let dcel = Dcel::build(&intersection(&mesh, &tolerances), &tolerances);
+        assert_eq!(dcel.faces_len(), 0);
+        assert!((0..dcel.half_edges_len()).all(|e| dcel.face(HalfEdgeId(e)).is_none()));
+    }
+
+    #[test]
+    fn collinear_departures_are_ordered_by_bend() {
+        // Both leave along the negative x axis, where `atan2` gives π for one and -π for the
+        // other, the first bending towards positive y.
+        let up = Dcel::departure_key(Vec2::new(-1., 0.), Vec2::new(-1., 0.5));
+        let down = Dcel::departure_key(Vec2::new(-1., -0.), Vec2::new(-1., -0.5));
+        assert_eq!(up.0, down.0);
+        assert!(up < down);
+
+        // Just past the wraparound the order starts over, which is still next to both of them
+        // going around the vertex.
+        let past = Dcel::departure_key(Vec2::new(-1., -0.1), Vec2::new(-1., -0.1));
+        assert!(past < up);
+    }
+}
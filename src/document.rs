@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dynamic::{DynamicRegions, intersection},
+    mesh::MMesh,
+    tolerance::Tolerances,
+};
+
+/// A drawing as saved to disk: the mesh and the regions styled on it.
+///
+/// Regions are not stored by index but by their boundary structure, so loading rebuilds the
+/// regions of the mesh and hands the styles over with [`DynamicRegions::style`], as after any
+/// other edit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Document {
+    pub mesh: MMesh,
+    styled: DynamicRegions,
+}
+
+impl Document {
+    pub fn new(mesh: &MMesh, regions: &DynamicRegions) -> Self {
+        Self {
+            mesh: mesh.clone(),
+            styled: regions.styled_regions(),
+        }
+    }
+
+    /// Regions of the saved mesh with the saved styles applied.
+    pub fn regions(&self, tolerances: &Tolerances) -> DynamicRegions {
+        DynamicRegions::build(intersection(&self.mesh, tolerances), tolerances)
+            .filter_outer_regions()
+            .style(self.styled.clone(), self.mesh.lineage(), tolerances)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Point, Shape};
+
+    use super::*;
+    use crate::{color::RED, mesh::fixtures::square_with_diagonal, style::Style};
+
+    #[test]
+    fn styles_survive_a_round_trip() {
+        let (mesh, _) = square_with_diagonal(100.);
+
+        let tolerances = Tolerances::default();
+        let mut regions = DynamicRegions::build(intersection(&mesh, &tolerances), &tolerances)
+            .filter_outer_regions();
+        let style = Style::solid(RED).with_opacity(0.5);
+        regions.apply_style(Some(style.clone()), Point::new(70., 30.));
+
+        let json = Document::new(&mesh, &regions).to_json().unwrap();
+        let loaded = Document::from_json(&json).unwrap().regions(&tolerances);
+        assert_eq!(loaded.len(), 2);
+        for i in 0..loaded.len() {
+            let inside = loaded.region_path(i).contains(Point::new(70., 30.));
+            assert_eq!(loaded.region_style(i), inside.then_some(&style));
+        }
+    }
+}
@@ -1,24 +1,124 @@
 use std::{
-    collections::{HashMap, HashSet},
-    f64::consts::PI,
+    collections::BTreeMap,
     fmt::{Display, Write},
+    ops::Range,
 };
 
+#[cfg(feature = "macroquad")]
+use kurbo::Affine;
 use kurbo::{
-    Affine, BezPath, Line, ParamCurve, ParamCurveFit, PathEl, PathSeg, Point, Rect, Shape, Vec2,
+    BezPath, Line, ParamCurve, ParamCurveArclen, ParamCurveNearest, PathEl, PathSeg, Point, Rect,
+    Shape, Vec2,
 };
+#[cfg(feature = "macroquad")]
 use macroquad::{
-    color::{BLACK, BLUE, BROWN, Color, GREEN, ORANGE, PURPLE, RED, YELLOW},
-    math::DVec2,
-    shapes::{draw_line, draw_rectangle},
+    color::{BLACK, WHITE},
+    shapes::draw_line,
+    texture::{FilterMode, Texture2D, draw_texture},
 };
 
 use crate::{
-    MIN_SEPARATION,
-    algo::{cleanup_intersections, pathseg_intersections},
+    algo::{
+        cleanup_intersections, lerp_range, min_cost_assignment, overlapping_boxes,
+        pathseg_intersections, pathseg_overlaps, pathseg_self_intersection,
+    },
+    dcel::Dcel,
+    lineage::Lineage,
     mesh::{MMesh, SegmentId},
-    util::{pathseg_tangent, segment_data_to_pathseg, xdraw_circle, xdraw_line, xdraw_segment},
+    raster::{Canvas, FLATTEN_TOLERANCE, FillRule, flatten_edges, scanline_spans},
+    style::Style,
+    tolerance::Tolerances,
+    util::{map_indices, segment_data_to_pathseg},
 };
+#[cfg(feature = "macroquad")]
+use crate::{color::BLANK, util::xdraw_circle};
+
+/// Lowest boundary similarity at which a region keeps the style of a previous region.
+pub const MATCH_THRESHOLD: f64 = 0.5;
+
+/// Share of [`Tolerances::separation`] region centroids are computed to when they are only
+/// compared with each other.
+const TIE_BREAK_ACCURACY: f64 = 0.1;
+
+/// Share of the similarity kept by pairs whose boundaries run through their parents in a
+/// different order. Such a pair needs to be that much more similar to win over a pair in the
+/// same order, and to reach [`MATCH_THRESHOLD`].
+const ORDER_MISMATCH_WEIGHT: f64 = 0.75;
+
+/// Cost added per unit of distance between the centroids of matched regions, small enough to
+/// only ever decide between equally similar pairs.
+const TIE_BREAK_WEIGHT: f64 = 1e-9;
+
+/// Finest accuracy boundary lengths are measured to, they only weigh boundary pieces.
+const LENGTH_ACCURACY: f64 = 1e-3;
+
+/// Samples along a sub-segment when looking for where it first leaves the reach of its own end.
+const GAP_SAMPLES: usize = 64;
+
+/// Scanlines the overlap of two regions is measured on, spread over their common height.
+const OVERLAP_ROWS: usize = 64;
+
+/// Accuracy regions are flattened to for measuring overlaps, relative to the size of the overlap.
+const OVERLAP_ACCURACY: f64 = 1e-3;
+
+/// What a region cut out of a styled region gets when no previous region matches it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitPolicy {
+    /// Left unstyled, only the piece matching the previous boundary best keeps the style.
+    #[default]
+    Unstyled,
+    /// Takes the style of the previous region it lies in.
+    Inherit,
+}
+
+/// Which style a region covering several styled regions gets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Style of the previous region whose boundary matches best, if any.
+    #[default]
+    BestMatch,
+    /// Style of the largest of the previous regions.
+    Largest,
+    /// Style applied most recently among the previous regions.
+    MostRecent,
+}
+
+/// How [`DynamicRegions::style_with_policy`] hands styles over when regions are cut in pieces or
+/// joined together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StylePolicy {
+    pub split: SplitPolicy,
+    pub merge: MergePolicy,
+    /// Share of a region that has to lie inside another to count as a piece of it.
+    pub min_overlap: f64,
+}
+
+impl Default for StylePolicy {
+    fn default() -> Self {
+        Self {
+            split: SplitPolicy::default(),
+            merge: MergePolicy::default(),
+            min_overlap: 0.9,
+        }
+    }
+}
+
+impl StylePolicy {
+    pub fn with_split(mut self, split: SplitPolicy) -> Self {
+        self.split = split;
+        self
+    }
+
+    pub fn with_merge(mut self, merge: MergePolicy) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    pub fn with_min_overlap(mut self, min_overlap: f64) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
@@ -26,12 +126,20 @@ pub enum Direction {
     EndToStart,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct IntersectData {
     /// Sub-segments
     pub segments: Vec<PathSeg>,
     /// Parent segments id for each sub-segment.
     pub parents: Vec<SegmentId>,
+    /// Every segment a sub-segment lies on, starting with its parent. Overlapping segments share a
+    /// single sub-segment along the stretch they have in common.
+    pub all_parents: Vec<Vec<SegmentId>>,
+    /// Parameter interval each sub-segment covers on its parent.
+    pub intervals: Vec<Range<f64>>,
+    /// Whether each sub-segment is a bridge added by [`Self::close_gaps`] rather than part of a
+    /// stroke.
+    pub bridges: Vec<bool>,
 }
 
 impl Display for IntersectData {
@@ -50,17 +158,156 @@ impl IntersectData {
         Self {
             segments: Vec::new(),
             parents: Vec::new(),
+            all_parents: Vec::new(),
+            intervals: Vec::new(),
+            bridges: Vec::new(),
         }
     }
 
-    fn push(&mut self, segment: PathSeg, parent: SegmentId) {
+    fn push(&mut self, segment: PathSeg, parent: SegmentId, interval: Range<f64>) {
         self.segments.push(segment);
         self.parents.push(parent);
+        self.all_parents.push(vec![parent]);
+        self.intervals.push(interval);
+        self.bridges.push(false);
+    }
+
+    fn push_bridge(&mut self, from: Point, to: Point, parent: SegmentId, t: f64) {
+        self.push(PathSeg::Line(Line::new(from, to)), parent, t..t);
+        *self.bridges.last_mut().unwrap() = true;
     }
 
+    /// Bridges the gaps up to [`Tolerances::gap`] wide between loose ends of sub-segments and
+    /// the rest of the drawing with straight sub-segments, so shapes drawn almost closed still
+    /// make regions.
+    ///
+    /// A loose end is joined to whatever is closest in reach: another loose end, or a point of a
+    /// sub-segment, which is split there. That includes its own sub-segment away from the end, so
+    /// a single curve drawn almost closed closes too. Since nothing is closer than the point a
+    /// bridge goes to, bridges never cross the drawing. Bridges are marked in [`Self::bridges`]
+    /// and take the parent of the loose end they start from, covering no parameter range on it.
+    pub fn close_gaps(&mut self, tolerances: &Tolerances) {
+        let separation = tolerances.separation;
+        let ends = (0..self.segments.len())
+            .flat_map(|i| {
+                let interval = &self.intervals[i];
+                [
+                    (self.segments[i].start(), i, interval.start, 0.),
+                    (self.segments[i].end(), i, interval.end, 1.),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mut loose = ends
+            .iter()
+            .filter(|(point, ..)| {
+                ends.iter()
+                    .filter(|(other, ..)| other.distance(*point) < separation)
+                    .count()
+                    == 1
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut bridged = vec![false; loose.len()];
+        for i in 0..loose.len() {
+            let (point, segment, t, end) = loose[i];
+            if bridged[i] {
+                continue;
+            }
+            let parent = self.parents[segment];
+            let mate = (0..loose.len())
+                .filter(|&j| j != i && !bridged[j])
+                .filter(|&j| {
+                    loose[j].1 != segment || !matches!(self.segments[segment], PathSeg::Line(_))
+                })
+                .map(|j| (j, loose[j].0.distance(point)))
+                .filter(|&(_, distance)| distance <= tolerances.gap)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let closest = (0..self.segments.len())
+                .filter_map(|k| {
+                    let (t, distance) = if k == segment {
+                        self.nearest_away_from(k, end, point, tolerances)?
+                    } else {
+                        let nearest = self.segments[k].nearest(point, tolerances.accuracy);
+                        (nearest.t, nearest.distance_sq.sqrt())
+                    };
+                    (distance <= tolerances.gap).then_some((k, t, distance))
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2));
+
+            // A point of a sub-segment next to the mate is the mate itself.
+            let to_segment = closest.filter(|&(k, t, distance)| {
+                mate.is_none_or(|(j, mate_distance)| {
+                    distance < mate_distance
+                        && self.segments[k].eval(t).distance(loose[j].0) >= separation
+                })
+            });
+            if let (Some((j, _)), None) = (mate, to_segment) {
+                bridged[i] = true;
+                bridged[j] = true;
+                self.push_bridge(point, loose[j].0, parent, t);
+                continue;
+            }
+            let Some((k, split_t, _)) = to_segment else {
+                continue;
+            };
+            bridged[i] = true;
+            let target = self.segments[k].eval(split_t);
+            if target.distance(self.segments[k].start()) >= separation
+                && target.distance(self.segments[k].end()) >= separation
+            {
+                let interval = self.intervals[k].clone();
+                let split = lerp_range(&interval, split_t);
+                let (all_parents, bridge) = (self.all_parents[k].clone(), self.bridges[k]);
+                self.push(
+                    self.segments[k].subsegment(split_t..1.),
+                    self.parents[k],
+                    split..interval.end,
+                );
+                *self.all_parents.last_mut().unwrap() = all_parents;
+                *self.bridges.last_mut().unwrap() = bridge;
+                self.segments[k] = self.segments[k].subsegment(0.0..split_t);
+                self.intervals[k] = interval.start..split;
+                // The end of `k` now belongs to the tail.
+                let tail = self.segments.len() - 1;
+                for entry in loose
+                    .iter_mut()
+                    .filter(|entry| entry.1 == k && entry.3 == 1.)
+                {
+                    entry.1 = tail;
+                }
+            }
+            // Closer than the separation the split point and the loose end already snap together.
+            if target.distance(point) >= separation {
+                self.push_bridge(point, target, parent, t);
+            }
+        }
+    }
+
+    /// Parameter and distance of the point of sub-segment `k` closest to its own `end`, 0 for its
+    /// start and 1 for its end, leaving out the stretch leading away from it up to
+    /// [`Tolerances::gap`].
+    fn nearest_away_from(
+        &self,
+        k: usize,
+        end: f64,
+        point: Point,
+        tolerances: &Tolerances,
+    ) -> Option<(f64, f64)> {
+        let curve = self.segments[k];
+        let away = |s: f64| end + (1. - 2. * end) * s;
+        let cut = (1..=GAP_SAMPLES)
+            .map(|step| step as f64 / GAP_SAMPLES as f64)
+            .find(|&s| curve.eval(away(s)).distance(point) > tolerances.gap)?;
+        let range = if end == 0. { cut..1. } else { 0.0..1. - cut };
+        let nearest = curve
+            .subsegment(range.clone())
+            .nearest(point, tolerances.accuracy);
+        Some((lerp_range(&range, nearest.t), nearest.distance_sq.sqrt()))
+    }
+
+    #[cfg(feature = "macroquad")]
     pub fn draw(&self) {
-        println!("intersection");
-        println!("len: {}", self.segments.len());
         for seg in &self.segments {
             xdraw_circle(seg.start(), 3., BLACK);
             xdraw_circle(seg.end(), 3., BLACK);
@@ -68,92 +315,275 @@ impl IntersectData {
     }
 }
 
-pub fn intersection(mesh: &MMesh) -> IntersectData {
-    let segments_data = mesh.segments_data();
+pub fn intersection(mesh: &MMesh, tolerances: &Tolerances) -> IntersectData {
+    // Segments are visited in table order so the sub-segments, and everything built from them,
+    // come out the same on every run.
+    let segments_data = mesh.segments();
     let points_data = mesh.points_data();
 
+    let segments = segments_data
+        .iter()
+        .map(|&data| segment_data_to_pathseg(&points_data, data, Direction::StartToEnd))
+        .collect::<Vec<PathSeg>>();
+
     // get all the intersection for each segment with every other segment in the mesh.
-    let mut segments_intersections = Vec::new();
+    let mut segments_intersections = vec![Vec::new(); segments.len()];
+    // Earlier segments each segment runs along for a stretch.
+    let mut overlapping = vec![Vec::new(); segments.len()];
+
+    // Only segments with overlapping bounding boxes can meet.
+    let boxes = segments
+        .iter()
+        .map(|segment| segment.bounding_box())
+        .collect::<Vec<Rect>>();
+    let mut candidates = vec![Vec::new(); segments.len()];
+    for (i, j) in overlapping_boxes(&boxes, tolerances.accuracy) {
+        candidates[i].push(j);
+    }
 
-    for iseg_id in segments_data.keys() {
-        let mut intersections = Vec::new();
-        for jseg_id in segments_data.keys() {
-            if iseg_id == jseg_id {
-                continue;
+    // Pairs are tested independently, possibly in parallel, and gathered in order afterwards so
+    // the result does not depend on scheduling.
+    let hits = map_indices(segments.len(), |i| {
+        let pairs = candidates[i]
+            .iter()
+            .map(|&j| (j, split_pair(segments[i], segments[j], tolerances.accuracy)))
+            .collect::<Vec<_>>();
+        (pathseg_self_intersection(segments[i]), pairs)
+    });
+
+    for (i, (self_intersection, pairs)) in hits.into_iter().enumerate() {
+        // A cubic crossing itself encloses a loop of its own.
+        if let Some((s, t)) = self_intersection {
+            segments_intersections[i].extend([s, t]);
+        }
+        for (j, (splits, overlap)) in pairs {
+            for (ti, tj) in splits {
+                segments_intersections[i].push(ti);
+                segments_intersections[j].push(tj);
+            }
+            if overlap {
+                overlapping[j].push(i);
             }
-            let iseg_data = segments_data[iseg_id];
-            let jseg_data = segments_data[jseg_id];
-
-            let iseg = segment_data_to_pathseg(&points_data, iseg_data, Direction::StartToEnd);
-            let jseg = segment_data_to_pathseg(&points_data, jseg_data, Direction::StartToEnd);
-
-            let mut intersection = pathseg_intersections(iseg, jseg);
-            intersections.append(&mut intersection);
         }
-
-        segments_intersections.push((iseg_id, intersections));
     }
 
     let mut intersection_data = IntersectData::new();
+    // Sub-segments produced by each segment.
+    let mut produced: Vec<Range<usize>> = Vec::with_capacity(segments.len());
 
-    for (seg_id, mut intersections) in segments_intersections {
-        intersections.push(1.);
-        intersections.push(0.);
-        intersections.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let intersections = cleanup_intersections(intersections);
-        println!("{:?}", &intersections);
-
-        // TODO: cleanup intersecitons
+    for (i, intersections) in segments_intersections.into_iter().enumerate() {
+        let intersections =
+            cleanup_intersections(segments[i], intersections, tolerances.separation);
 
+        let first = intersection_data.segments.len();
         let mut last_t = 0.;
         for &next_t in intersections.iter().skip(1) {
-            let segment = segments_data[seg_id];
-            let segment = segment_data_to_pathseg(&points_data, segment, Direction::StartToEnd);
-            let subsegment = segment.subsegment(last_t..next_t);
+            let subsegment = segments[i].subsegment(last_t..next_t);
 
-            intersection_data.push(subsegment, *seg_id);
+            let shared = overlapping[i]
+                .iter()
+                .flat_map(|&other| produced[other].clone())
+                .find(|&k| {
+                    same_curve(
+                        intersection_data.segments[k],
+                        subsegment,
+                        tolerances.separation,
+                    )
+                });
+            match shared {
+                Some(k) => intersection_data.all_parents[k].push(segments_data[i].id),
+                None => intersection_data.push(subsegment, segments_data[i].id, last_t..next_t),
+            }
 
             last_t = next_t;
         }
+        produced.push(first..intersection_data.segments.len());
     }
 
     intersection_data
 }
 
-pub struct MergeData {
-    num: usize,
-}
+/// Parameters two segments should be split at where they meet, and whether they overlap.
+fn split_pair(seg1: PathSeg, seg2: PathSeg, accuracy: f64) -> (Vec<(f64, f64)>, bool) {
+    let overlaps = pathseg_overlaps(seg1, seg2, accuracy);
+    if overlaps.is_empty() {
+        let splits = pathseg_intersections(seg1, seg2, accuracy)
+            .into_iter()
+            .map(|(t1, t2, _)| (t1, t2))
+            .collect();
+        return (splits, false);
+    }
 
-pub fn merge(intersect_data: IntersectData) -> MergeData {
-    todo!()
+    // Split at the ends of the shared stretches, and wherever the parts outside of them cross.
+    let mut splits = overlaps
+        .iter()
+        .flat_map(|(range1, range2)| [(range1.start, range2.start), (range1.end, range2.end)])
+        .collect::<Vec<_>>();
+    let outside1 = outside_ranges(overlaps.iter().map(|(range1, _)| range1.clone()));
+    let outside2 = outside_ranges(overlaps.iter().map(|(_, range2)| range2.clone()));
+    for range1 in &outside1 {
+        for range2 in &outside2 {
+            let (part1, part2) = (
+                seg1.subsegment(range1.clone()),
+                seg2.subsegment(range2.clone()),
+            );
+            splits.extend(
+                pathseg_intersections(part1, part2, accuracy)
+                    .into_iter()
+                    .map(|(t1, t2, _)| (lerp_range(range1, t1), lerp_range(range2, t2))),
+            );
+        }
+    }
+    (splits, true)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Flow {
-    StartToEnd,
-    EndToStart,
+/// Parts of `0..1` not covered by any of `ranges`, which may run either way.
+fn outside_ranges(ranges: impl Iterator<Item = Range<f64>>) -> Vec<Range<f64>> {
+    let mut covered = ranges
+        .map(|range| range.start.min(range.end)..range.start.max(range.end))
+        .collect::<Vec<_>>();
+    covered.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut outside = Vec::new();
+    let mut start = 0.;
+    for range in covered {
+        if range.start > start {
+            outside.push(start..range.start);
+        }
+        start = f64::max(start, range.end);
+    }
+    if start < 1. {
+        outside.push(start..1.);
+    }
+    outside
 }
 
-impl Flow {
-    fn start(&self) -> f64 {
-        match *self {
-            Flow::StartToEnd => 0.,
-            Flow::EndToStart => 1.,
+/// Centroid of the area enclosed by `path`, from the flattened outline. Subpaths running the
+/// other way, like holes, count negatively. Degenerate paths give the average of their points.
+fn centroid(path: &BezPath, accuracy: f64) -> Point {
+    let mut polygons: Vec<Vec<Point>> = Vec::new();
+    kurbo::flatten(path, accuracy, |element| match element {
+        PathEl::MoveTo(point) => polygons.push(vec![point]),
+        PathEl::LineTo(point) => polygons.last_mut().unwrap().push(point),
+        PathEl::ClosePath => {}
+        _ => unreachable!("flatten only emits lines"),
+    });
+
+    let (mut area, mut x, mut y) = (0., 0., 0.);
+    for polygon in &polygons {
+        for (i, &p0) in polygon.iter().enumerate() {
+            let p1 = polygon[(i + 1) % polygon.len()];
+            let cross = p0.to_vec2().cross(p1.to_vec2());
+            area += cross;
+            x += (p0.x + p1.x) * cross;
+            y += (p0.y + p1.y) * cross;
         }
     }
 
-    fn end(&self) -> f64 {
-        match *self {
-            Flow::StartToEnd => 1.,
-            Flow::EndToStart => 0.,
+    if area.abs() < 1e-12 {
+        let points = polygons.iter().flatten().collect::<Vec<_>>();
+        let sum = points
+            .iter()
+            .fold(Vec2::ZERO, |sum, point| sum + point.to_vec2());
+        return (sum / points.len().max(1) as f64).to_point();
+    }
+    Point::new(x / (3. * area), y / (3. * area))
+}
+
+/// Whether two sub-segments trace the same curve, in either direction, within `tolerance`.
+fn same_curve(a: PathSeg, b: PathSeg, tolerance: f64) -> bool {
+    let ends_match = (a.start().distance(b.start()) < tolerance
+        && a.end().distance(b.end()) < tolerance)
+        || (a.start().distance(b.end()) < tolerance && a.end().distance(b.start()) < tolerance);
+    ends_match
+        && [0.25, 0.5, 0.75]
+            .iter()
+            .all(|&t| b.nearest(a.eval(t), tolerance * 0.1).distance_sq < tolerance * tolerance)
+}
+
+/// Area inside both `a` and `b` with the nonzero rule, summed over [`OVERLAP_ROWS`] scanlines.
+fn overlap_area(a: &BezPath, b: &BezPath) -> f64 {
+    let common = a.bounding_box().intersect(b.bounding_box());
+    if common.width() <= 0. || common.height() <= 0. {
+        return 0.;
+    }
+    let accuracy = Vec2::new(common.width(), common.height()).hypot() * OVERLAP_ACCURACY;
+    let (a, b) = (flatten_edges(a, accuracy), flatten_edges(b, accuracy));
+    let step = common.height() / OVERLAP_ROWS as f64;
+
+    let mut area = 0.;
+    for row in 0..OVERLAP_ROWS {
+        let y = common.y0 + (row as f64 + 0.5) * step;
+        let (a, b) = (
+            scanline_spans(&a, y, FillRule::NonZero),
+            scanline_spans(&b, y, FillRule::NonZero),
+        );
+        // Both lists are sorted and disjoint, so one pass pairs up every overlapping span.
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            area += (a[i].1.min(b[j].1) - a[i].0.max(b[j].0)).max(0.) * step;
+            if a[i].1 < b[j].1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
         }
     }
+    area
+}
+
+/// Pairs each new region with at most one previous region by maximising the total similarity,
+/// among equally similar pairs the one with the closest centroids.
+///
+/// Pairs below [`MATCH_THRESHOLD`] count as not similar at all, so a pair that cannot match does
+/// not pull another region away from the previous region it would match.
+fn match_regions(
+    similarity: &[Vec<f64>],
+    curr_centroids: &[Point],
+    prev_centroids: &[Point],
+) -> Vec<Option<usize>> {
+    let similarity = similarity
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&s| if s >= MATCH_THRESHOLD { s } else { 0. })
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<_>>();
+    let cost = map_indices(similarity.len(), |i| {
+        (0..prev_centroids.len())
+            .map(|j| {
+                let distance = curr_centroids[i].distance(prev_centroids[j]);
+                1. - similarity[i][j] + distance * TIE_BREAK_WEIGHT
+            })
+            .collect::<Vec<f64>>()
+    });
+
+    min_cost_assignment(&cost)
+        .into_iter()
+        .enumerate()
+        .map(|(i, assigned)| assigned.filter(|&j| similarity[i][j] > 0.))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Flow {
+    StartToEnd,
+    EndToStart,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DynamicRegionStructure {
     parent: Vec<SegmentId>,
     flow: Vec<Flow>,
+    /// Length of the sub-segment each boundary piece runs along.
+    length: Vec<f64>,
+    /// Parameter interval of each boundary piece on its parent.
+    interval: Vec<Range<f64>>,
+    /// Where each boundary cycle starts, the outer boundary first and then the holes.
+    cycles: Vec<usize>,
 }
 
 impl DynamicRegionStructure {
@@ -161,39 +591,235 @@ impl DynamicRegionStructure {
         Self {
             parent: Vec::new(),
             flow: Vec::new(),
+            length: Vec::new(),
+            interval: Vec::new(),
+            cycles: vec![0],
         }
     }
 
-    fn push(&mut self, parent: SegmentId, flow: Flow) {
+    fn push(&mut self, parent: SegmentId, flow: Flow, length: f64, interval: Range<f64>) {
         self.parent.push(parent);
         self.flow.push(flow);
+        self.length.push(length);
+        self.interval.push(interval);
+    }
+
+    /// Adds the cycles of `other` as further cycles of this boundary.
+    fn extend(&mut self, other: &DynamicRegionStructure) {
+        let offset = self.parent.len();
+        self.cycles
+            .extend(other.cycles.iter().map(|start| start + offset));
+        self.parent.extend_from_slice(&other.parent);
+        self.flow.extend_from_slice(&other.flow);
+        self.length.extend_from_slice(&other.length);
+        self.interval.extend_from_slice(&other.interval);
     }
 
-    fn match_structure(&self, other: &DynamicRegionStructure) -> bool {
-        let is_match = self
-            .flow
+    /// Same boundary in terms of the segments its parents turned into. A parent replaced by
+    /// several segments spreads its length evenly over them, and as the parameters on the new
+    /// segments are unknown, each piece covers the whole of them.
+    fn resolve(&self, lineage: &Lineage) -> DynamicRegionStructure {
+        let mut resolved = DynamicRegionStructure::new();
+        resolved.cycles.clear();
+        for i in 0..self.parent.len() {
+            if self.cycles.contains(&i) {
+                resolved.cycles.push(resolved.parent.len());
+            }
+            let successors = lineage.resolve(self.parent[i]);
+            let share = self.length[i] / successors.len() as f64;
+            for (successor, reversed) in successors {
+                let flow = match (self.flow[i], reversed) {
+                    (flow, false) => flow,
+                    (Flow::StartToEnd, true) => Flow::EndToStart,
+                    (Flow::EndToStart, true) => Flow::StartToEnd,
+                };
+                let interval = if successor == self.parent[i] {
+                    self.interval[i].clone()
+                } else {
+                    0.0..1.0
+                };
+                resolved.push(successor, flow, share, interval);
+            }
+        }
+        if resolved.cycles.is_empty() {
+            resolved.cycles.push(0);
+        }
+        resolved
+    }
+
+    /// Order-aware view of the boundary.
+    pub fn signature(&self) -> BoundarySignature {
+        let ends = self.cycles[1..].iter().copied().chain([self.parent.len()]);
+        let cycles = self
+            .cycles
             .iter()
-            .zip(self.parent.iter())
-            .all(|(&flow, &parent)| {
-                other
-                    .flow
-                    .iter()
-                    .zip(other.parent.iter())
-                    .any(|(&o_flow, &o_parent)| flow == o_flow && parent == o_parent)
-            });
+            .zip(ends)
+            .map(|(&start, end)| {
+                let pieces = (start..end).map(|i| BoundaryPiece {
+                    parent: self.parent[i],
+                    flow: self.flow[i],
+                    interval: self.interval[i].clone(),
+                });
+                BoundarySignature::merge_pieces(pieces.collect())
+            })
+            .collect();
+        BoundarySignature { cycles }
+    }
 
-        if self.flow.len() == other.flow.len() && is_match {
-            true
-        } else {
-            false
+    /// Boundary length running along each parent segment in each direction, ordered so sums over
+    /// them come out the same every time.
+    fn weights(&self) -> BTreeMap<(SegmentId, Flow), f64> {
+        let mut weights = BTreeMap::new();
+        for ((&parent, &flow), &length) in self.parent.iter().zip(&self.flow).zip(&self.length) {
+            *weights.entry((parent, flow)).or_default() += length;
+        }
+        weights
+    }
+
+    /// Weighted Jaccard similarity of the two boundaries, from `0` for boundaries sharing no parent
+    /// segment to `1` for boundaries running the same length along the same parents.
+    ///
+    /// Unlike comparing the pieces one to one, adding a stroke that splits one piece of the
+    /// boundary, or cuts a little off it, only lowers the score by the length involved.
+    pub fn similarity(&self, other: &DynamicRegionStructure) -> f64 {
+        let (weights, other_weights) = (self.weights(), other.weights());
+        let (mut shared, mut total) = (0., 0.);
+        for (key, &weight) in &weights {
+            let other_weight = other_weights.get(key).copied().unwrap_or(0.);
+            shared += weight.min(other_weight);
+            total += weight.max(other_weight);
+        }
+        for (key, &other_weight) in &other_weights {
+            if !weights.contains_key(key) {
+                total += other_weight;
+            }
+        }
+        if total > 0. { shared / total } else { 0. }
+    }
+}
+
+/// One piece of a region boundary: the parent segment it runs along, which way, and over which
+/// parameter interval of the parent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundaryPiece {
+    pub parent: SegmentId,
+    pub flow: Flow,
+    pub interval: Range<f64>,
+}
+
+impl BoundaryPiece {
+    /// Same parent, same way and overlapping intervals. Intervals only need to overlap, as edits
+    /// nearby move the points a parent is split at.
+    fn matches(&self, other: &BoundaryPiece) -> bool {
+        self.parent == other.parent
+            && self.flow == other.flow
+            && self.interval.start < other.interval.end
+            && other.interval.start < self.interval.end
+    }
+
+    /// Whether `next` carries on along the parent right where this piece stops.
+    fn continues_into(&self, next: &BoundaryPiece) -> bool {
+        self.parent == next.parent
+            && self.flow == next.flow
+            && match self.flow {
+                Flow::StartToEnd => self.interval.end == next.interval.start,
+                Flow::EndToStart => self.interval.start == next.interval.end,
+            }
+    }
+}
+
+/// Boundary of a region as cyclic sequences of [`BoundaryPiece`]s, the outer boundary first and
+/// then the holes.
+///
+/// Unlike [`DynamicRegionStructure::similarity`], which sees the boundary as a set, this tells
+/// apart faces made of the same parents in a different order, as happens when one long stroke
+/// crosses itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundarySignature {
+    cycles: Vec<Vec<BoundaryPiece>>,
+}
+
+impl BoundarySignature {
+    /// Joins consecutive pieces running on along the same parent, including around the end of
+    /// the cycle, so a stroke ending on the boundary does not change the signature.
+    fn merge_pieces(pieces: Vec<BoundaryPiece>) -> Vec<BoundaryPiece> {
+        let mut merged: Vec<BoundaryPiece> = Vec::new();
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last) if last.continues_into(&piece) => {
+                    last.interval = last.interval.start.min(piece.interval.start)
+                        ..last.interval.end.max(piece.interval.end);
+                }
+                _ => merged.push(piece),
+            }
+        }
+        if merged.len() > 1 && merged.last().unwrap().continues_into(&merged[0]) {
+            let last = merged.pop().unwrap();
+            merged[0].interval = last.interval.start.min(merged[0].interval.start)
+                ..last.interval.end.max(merged[0].interval.end);
+        }
+        merged
+    }
+
+    /// Whether two cycles have matching pieces in the same order, starting anywhere.
+    fn cycles_match(a: &[BoundaryPiece], b: &[BoundaryPiece]) -> bool {
+        a.len() == b.len()
+            && (a.is_empty()
+                || (0..b.len()).any(|rotation| {
+                    (0..a.len()).all(|k| a[k].matches(&b[(k + rotation) % b.len()]))
+                }))
+    }
+
+    /// Whether the outer boundaries match up to rotation and every hole matches a different hole.
+    pub fn matches(&self, other: &BoundarySignature) -> bool {
+        if self.cycles.len() != other.cycles.len()
+            || !Self::cycles_match(&self.cycles[0], &other.cycles[0])
+        {
+            return false;
         }
+        let mut used = vec![false; other.cycles.len()];
+        self.cycles[1..].iter().all(|hole| {
+            let found = (1..other.cycles.len())
+                .find(|&j| !used[j] && Self::cycles_match(hole, &other.cycles[j]));
+            found.map(|j| used[j] = true).is_some()
+        })
     }
 }
 
-#[derive(Clone, Debug)]
+/// Which way a region's outer boundary runs, following the sign of its area. In screen coordinates,
+/// with y pointing down, positive is clockwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Positive,
+    Negative,
+}
+
+/// Geometric properties of a region, holes included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegionProperties {
+    /// Signed area, with the holes taken out.
+    pub area: f64,
+    pub centroid: Point,
+    /// Length of the outer boundary and of the holes.
+    pub perimeter: f64,
+    pub bounding_box: Rect,
+    pub orientation: Orientation,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DynamicRegions {
+    /// Outer boundary of each region.
     paths: Vec<BezPath>,
-    colors: Vec<Option<Color>>,
+    /// Inner boundaries of each region, made by shapes drawn inside it.
+    holes: Vec<Vec<BezPath>>,
+    /// Innermost region each region is drawn inside of, forming the containment tree.
+    enclosing: Vec<Option<usize>>,
+    /// Whether the region runs around the outside of a shape rather than enclosing an area.
+    outside: Vec<bool>,
+    styles: Vec<Option<Style>>,
+    /// When each region was styled, higher being more recent.
+    styled_at: Vec<usize>,
     structures: Vec<DynamicRegionStructure>,
 }
 
@@ -201,306 +827,977 @@ impl DynamicRegions {
     pub fn new() -> Self {
         Self {
             paths: Vec::new(),
-            colors: Vec::new(),
+            holes: Vec::new(),
+            enclosing: Vec::new(),
+            outside: Vec::new(),
+            styles: Vec::new(),
+            styled_at: Vec::new(),
             structures: Vec::new(),
         }
     }
 
-    fn push(&mut self, path: BezPath, structure: DynamicRegionStructure) {
+    fn push(&mut self, path: BezPath, structure: DynamicRegionStructure, outside: bool) {
         self.paths.push(path);
+        self.holes.push(Vec::new());
+        self.enclosing.push(None);
+        self.outside.push(outside);
         self.structures.push(structure);
-        self.colors.push(None);
+        self.styles.push(None);
+        self.styled_at.push(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn holes(&self, region: usize) -> &[BezPath] {
+        &self.holes[region]
+    }
+
+    pub fn enclosing(&self, region: usize) -> Option<usize> {
+        self.enclosing[region]
+    }
+
+    pub fn is_outside(&self, region: usize) -> bool {
+        self.outside[region]
+    }
+
+    /// Regions directly inside `region`.
+    pub fn children(&self, region: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(move |&i| self.enclosing[i] == Some(region))
+    }
+
+    /// Outline of a region with its holes, to be filled with [`FillRule::NonZero`]. Holes run
+    /// opposite to the outer boundary so they cancel out.
+    pub fn region_path(&self, region: usize) -> BezPath {
+        let mut path = self.paths[region].clone();
+        for hole in &self.holes[region] {
+            path.extend(hole);
+        }
+        path
     }
 
-    pub fn render(&self) {
-        println!("paths {:?}", self.paths);
-        println!("paths len {}", self.paths.len());
-        for (i, path) in self.paths.clone().iter().enumerate() {
-            if path.elements().is_empty() {
+    pub fn region_style(&self, region: usize) -> Option<&Style> {
+        self.styles[region].as_ref()
+    }
+
+    pub fn area(&self, region: usize) -> f64 {
+        self.region_path(region).area()
+    }
+
+    /// Properties of a region, with curves measured to within `accuracy`.
+    pub fn properties(&self, region: usize, accuracy: f64) -> RegionProperties {
+        let path = self.region_path(region);
+        let orientation = if self.paths[region].area() < 0. {
+            Orientation::Negative
+        } else {
+            Orientation::Positive
+        };
+        RegionProperties {
+            area: path.area(),
+            centroid: centroid(&path, accuracy),
+            perimeter: path.perimeter(accuracy),
+            bounding_box: self.paths[region].bounding_box(),
+            orientation,
+        }
+    }
+
+    /// Keeps the regions `keep` accepts, pointing regions whose enclosing region is dropped at
+    /// the top level.
+    fn retain_regions(&self, keep: impl Fn(usize) -> bool) -> Self {
+        let mut index = vec![None; self.len()];
+        let mut result = DynamicRegions::new();
+        for i in (0..self.len()).filter(|&i| keep(i)) {
+            index[i] = Some(result.len());
+            result.paths.push(self.paths[i].clone());
+            result.holes.push(self.holes[i].clone());
+            result.enclosing.push(self.enclosing[i]);
+            result.outside.push(self.outside[i]);
+            result.styles.push(self.styles[i].clone());
+            result.styled_at.push(self.styled_at[i]);
+            result.structures.push(self.structures[i].clone());
+        }
+        for enclosing in &mut result.enclosing {
+            *enclosing = enclosing.and_then(|i| index[i]);
+        }
+        result
+    }
+
+    /// Draws every styled region, solid fills as scanlines and other fills from the textures in
+    /// `fills`, which are only drawn again for regions whose outline or style changed.
+    #[cfg(feature = "macroquad")]
+    pub fn render(&self, fills: &mut FillCache) {
+        fills
+            .textures
+            .iter_mut()
+            .for_each(|texture| texture.used = false);
+        for (i, style) in self.styles.iter().enumerate() {
+            let Some(style) = style else {
                 continue;
-            }
-            let Some(color) = self.colors[i] else {
+            };
+            let path = self.region_path(i);
+            let Some(color) = style.uniform_color() else {
+                let texture = fills.texture(path, style, || self.properties(i, FLATTEN_TOLERANCE));
+                let (x, y) = (texture.origin.x as f32, texture.origin.y as f32);
+                draw_texture(&texture.texture, x, y, WHITE);
                 continue;
             };
-            println!("#");
-            let mut bbox = path.bounding_box().abs();
-            while bbox.y0 < bbox.y1 {
-                let line = Line::new(Point::new(bbox.x0, bbox.y0), Point::new(bbox.x1, bbox.y0));
-                let intersections = path.segments().fold(Vec::new(), |mut acc, seg| {
-                    acc.append(
-                        &mut seg
-                            .intersect_line(line)
-                            .iter()
-                            .map(|it| line.eval(it.line_t).x)
-                            .collect(),
-                    );
-                    acc
-                });
-                let min_x = intersections
-                    .iter()
-                    .min_by(|a, b| a.partial_cmp(&b).unwrap());
-                let max_x = intersections
-                    .iter()
-                    .max_by(|a, b| a.partial_cmp(&b).unwrap());
-
-                if let (Some(min_x), Some(max_x)) = (min_x, max_x) {
-                    draw_line(
-                        *min_x as f32,
-                        bbox.y0 as f32,
-                        *max_x as f32,
-                        bbox.y0 as f32,
-                        2.,
-                        color,
-                    );
+            let edges = flatten_edges(&path, FLATTEN_TOLERANCE);
+            let bbox = path.bounding_box();
+            let mut y = bbox.y0.floor() + 0.5;
+            while y < bbox.y1 {
+                for (x0, x1) in scanline_spans(&edges, y, FillRule::NonZero) {
+                    draw_line(x0 as f32, y as f32, x1 as f32, y as f32, 1., color.into());
                 }
-                bbox.y0 += 1.;
+                y += 1.;
             }
         }
+        fills.textures.retain(|texture| texture.used);
     }
 
-    pub fn build(intersect_data: IntersectData) -> DynamicRegions {
-        println!("{}", intersect_data);
-        let mut dynamic_regions = DynamicRegions::new();
-
-        let mut visited_start_to_end = HashSet::<usize>::new();
-        let mut visited_end_to_start = HashSet::<usize>::new();
-
-        for next_idx in 0..intersect_data.segments.len() {
-            for flow in [Flow::StartToEnd, Flow::EndToStart] {
-                let visited = match flow {
-                    Flow::StartToEnd => visited_start_to_end.contains(&next_idx),
-                    Flow::EndToStart => visited_end_to_start.contains(&next_idx),
-                };
+    pub fn rasterize(&self, canvas: &mut Canvas) {
+        for (i, style) in self.styles.iter().enumerate() {
+            let Some(style) = style else {
+                continue;
+            };
+            let path = self.region_path(i);
+            match style.uniform_color() {
+                Some(color) => canvas.fill_path(&path, color, FillRule::NonZero),
+                None => {
+                    let properties = self.properties(i, FLATTEN_TOLERANCE);
+                    canvas.fill_path_with(&path, FillRule::NonZero, |point| {
+                        style.color_at(point, &properties)
+                    });
+                }
+            }
+        }
+    }
 
-                if visited {
+    /// Builds one region per face of the planar subdivision of `intersect_data`.
+    ///
+    /// The face with the smallest signed area of every connected component runs around its
+    /// outside. When the component is drawn inside a region of another component, that outside
+    /// becomes a hole of the innermost such region. Otherwise it is kept as a region of its own.
+    pub fn build(mut intersect_data: IntersectData, tolerances: &Tolerances) -> DynamicRegions {
+        if tolerances.gap > 0. {
+            intersect_data.close_gaps(tolerances);
+        }
+        let dcel = Dcel::build(&intersect_data, tolerances);
+        let lengths = map_indices(intersect_data.segments.len(), |i| {
+            intersect_data.segments[i].arclen(tolerances.accuracy.max(LENGTH_ACCURACY))
+        });
+        let faces = dcel.faces().collect::<Vec<_>>();
+        let built = map_indices(faces.len(), |i| {
+            let mut structure = DynamicRegionStructure::new();
+            for (segment, flow) in dcel.face_segments(faces[i]) {
+                // Bridges belong to no stroke, so they play no part in telling regions apart.
+                if intersect_data.bridges[segment] {
                     continue;
                 }
+                structure.push(
+                    intersect_data.parents[segment],
+                    flow,
+                    lengths[segment],
+                    intersect_data.intervals[segment].clone(),
+                );
+            }
+            let path = dcel.face_path(faces[i]);
+            let area = path.area();
+            (path, structure, area)
+        });
+
+        let mut outer_faces: Vec<Option<usize>> = vec![None; dcel.components_len()];
+        for (i, &face) in faces.iter().enumerate() {
+            let outer = &mut outer_faces[dcel.face_component(face)];
+            if outer.is_none_or(|o| built[i].2 < built[o].2) {
+                *outer = Some(i);
+            }
+        }
+        let is_outer = |i: usize| outer_faces[dcel.face_component(faces[i])] == Some(i);
 
-                if let Some((bezpath, structure, visited_idxs)) =
-                    Self::build_region(&intersect_data, next_idx, flow)
-                {
-                    println!("{:#?}", structure);
-                    // update visited state
-                    for i in 0..visited_idxs.len() {
-                        let flow = structure.flow[i];
-                        let idx = visited_idxs[i];
-                        match flow {
-                            Flow::StartToEnd => visited_start_to_end.insert(idx),
-                            Flow::EndToStart => visited_end_to_start.insert(idx),
-                        };
-                    }
+        // Face each component is drawn inside of, if any.
+        let containers = outer_faces
+            .iter()
+            .enumerate()
+            .map(|(component, outer)| {
+                let inside = dcel.vertex(dcel.origin(dcel.face_boundary(faces[(*outer)?])[0]));
+                (0..faces.len())
+                    .filter(|&i| !is_outer(i) && dcel.face_component(faces[i]) != component)
+                    .filter(|&i| built[i].0.winding(inside) != 0)
+                    .min_by(|&a, &b| built[a].2.total_cmp(&built[b].2))
+            })
+            .collect::<Vec<Option<usize>>>();
+        // Strokes that enclose nothing, like a loose scribble, are drawn over the region they are
+        // in rather than cutting a hole in it.
+        let mut encloses = vec![false; dcel.components_len()];
+        for (i, &face) in faces.iter().enumerate() {
+            if !is_outer(i) && built[i].2 > 0. {
+                encloses[dcel.face_component(face)] = true;
+            }
+        }
+        let is_hole = |i: usize| {
+            let component = dcel.face_component(faces[i]);
+            is_outer(i) && encloses[component] && containers[component].is_some()
+        };
 
-                    dynamic_regions.push(bezpath, structure);
-                }
+        let mut region = vec![None; faces.len()];
+        let mut dynamic_regions = DynamicRegions::new();
+        for (i, (path, structure, _)) in built.iter().enumerate() {
+            if !is_hole(i) {
+                region[i] = Some(dynamic_regions.len());
+                dynamic_regions.push(path.clone(), structure.clone(), is_outer(i));
+            }
+        }
+
+        for (i, &face) in faces.iter().enumerate() {
+            let Some(container) = containers[dcel.face_component(face)] else {
+                continue;
+            };
+            let container = region[container].unwrap();
+            if is_hole(i) {
+                let (path, structure, _) = &built[i];
+                dynamic_regions.holes[container].push(path.clone());
+                dynamic_regions.structures[container].extend(structure);
+            } else {
+                dynamic_regions.enclosing[region[i].unwrap()] = Some(container);
             }
         }
 
         dynamic_regions
     }
 
-    fn build_region(
-        intersect_data: &IntersectData,
-        start: usize,
-        flow: Flow,
-    ) -> Option<(BezPath, DynamicRegionStructure, Vec<usize>)> {
-        let mut path = BezPath::new();
-        let mut structure = DynamicRegionStructure::new();
-        let mut visited_index = Vec::new();
+    /// Only the regions that have a style, which is all [`Self::style`] needs to carry them over.
+    pub fn styled_regions(&self) -> Self {
+        self.retain_regions(|i| self.styles[i].is_some())
+    }
 
-        let mut curr_idx = start;
-        let mut curr_flow = flow;
+    /// Drops the regions running around the outside of each separate shape, keeping every area
+    /// the shapes enclose.
+    pub fn filter_outer_regions(self) -> Self {
+        self.retain_regions(|i| !self.outside[i])
+    }
 
-        println!(
-            "====================================================> {}, {:?}",
-            curr_idx, curr_flow
-        );
-        // let mut i = 0;
-        loop {
-            let curr_segment = intersect_data.segments[curr_idx];
-            let curr_segment = match curr_flow {
-                Flow::StartToEnd => curr_segment,
-                Flow::EndToStart => curr_segment.reverse(),
-            };
-            let curr_parent = intersect_data.parents[curr_idx];
-            if path.elements().is_empty() {
-                path.move_to(curr_segment.start());
+    /// Carries the styles of `prev_dynamic_region` over to the regions they most likely became.
+    ///
+    /// Previous boundaries are first followed through `lineage`, so segments split, merged or
+    /// redrawn since still count as the same. Every new region is then paired with at most one
+    /// previous region by maximising the total [`DynamicRegionStructure::similarity`], weighed
+    /// down for pairs whose [`BoundarySignature`]s do not match. Pairs scoring below
+    /// [`MATCH_THRESHOLD`] are left out of the pairing, and among equally scoring pairs the closest
+    /// centroids win.
+    pub fn style(
+        self,
+        prev_dynamic_region: DynamicRegions,
+        lineage: &Lineage,
+        tolerances: &Tolerances,
+    ) -> Self {
+        self.style_with_policy(
+            prev_dynamic_region,
+            lineage,
+            &StylePolicy::default(),
+            tolerances,
+        )
+    }
+
+    /// Same as [`Self::style`], then hands the styles of regions cut in pieces or joined together
+    /// over as `policy` says, judging by how much of each region lies in the other.
+    pub fn style_with_policy(
+        mut self,
+        prev_dynamic_region: DynamicRegions,
+        lineage: &Lineage,
+        policy: &StylePolicy,
+        tolerances: &Tolerances,
+    ) -> Self {
+        let accuracy = tolerances.separation * TIE_BREAK_ACCURACY;
+        let centroids = |regions: &DynamicRegions| {
+            (0..regions.len())
+                .map(|i| regions.properties(i, accuracy).centroid)
+                .collect::<Vec<Point>>()
+        };
+        let (curr_centroids, prev_centroids) = (centroids(&self), centroids(&prev_dynamic_region));
+
+        let prev_structures = prev_dynamic_region
+            .structures
+            .iter()
+            .map(|structure| structure.resolve(lineage))
+            .collect::<Vec<_>>();
+        let prev_signatures = prev_structures
+            .iter()
+            .map(DynamicRegionStructure::signature)
+            .collect::<Vec<_>>();
+        let similarity = map_indices(self.len(), |i| {
+            let signature = self.structures[i].signature();
+            prev_structures
+                .iter()
+                .zip(prev_signatures.iter())
+                .map(|(prev_structure, prev_signature)| {
+                    let similarity = self.structures[i].similarity(prev_structure);
+                    if signature.matches(prev_signature) {
+                        similarity
+                    } else {
+                        similarity * ORDER_MISMATCH_WEIGHT
+                    }
+                })
+                .collect::<Vec<f64>>()
+        });
+        let matches = match_regions(&similarity, &curr_centroids, &prev_centroids);
+        for (i, matched) in matches.into_iter().enumerate() {
+            if let Some(idx) = matched {
+                self.styles[i] = prev_dynamic_region.styles[idx].clone();
+                self.styled_at[i] = prev_dynamic_region.styled_at[idx];
             }
-            path.push(curr_segment.as_path_el());
-            structure.push(curr_parent, curr_flow);
-            visited_index.push(curr_idx);
-
-            let curr_tangent = {
-                let a = curr_segment.eval(1.);
-                let b = curr_segment.eval(0.98);
-                (b - a).normalize()
-            };
-            let curr_angle = curr_tangent.angle();
-            let curr_angle = if curr_angle.is_sign_negative() {
-                2. * PI + curr_angle
+        }
+
+        if policy.split == SplitPolicy::Unstyled && policy.merge == MergePolicy::BestMatch {
+            return self;
+        }
+        let styled = (0..prev_dynamic_region.len())
+            .filter(|&j| prev_dynamic_region.styles[j].is_some())
+            .collect::<Vec<usize>>();
+        let prev_paths = styled
+            .iter()
+            .map(|&j| prev_dynamic_region.region_path(j))
+            .collect::<Vec<_>>();
+        let overlaps = map_indices(self.len(), |i| {
+            let path = self.region_path(i);
+            prev_paths
+                .iter()
+                .map(|prev_path| overlap_area(&path, prev_path))
+                .collect::<Vec<f64>>()
+        });
+
+        for (i, overlap) in overlaps.iter().enumerate() {
+            let inside =
+                |k: usize, area: f64| overlap[k] > 0. && overlap[k] >= policy.min_overlap * area;
+            // Previous regions lying in this one, which it was joined from.
+            let merged = (0..styled.len())
+                .filter(|&k| inside(k, prev_dynamic_region.area(styled[k]).abs()))
+                .collect::<Vec<usize>>();
+            let from = if merged.len() > 1 {
+                match policy.merge {
+                    MergePolicy::BestMatch => None,
+                    MergePolicy::Largest => merged
+                        .iter()
+                        .copied()
+                        .max_by(|&a, &b| overlap[a].total_cmp(&overlap[b])),
+                    MergePolicy::MostRecent => merged
+                        .iter()
+                        .copied()
+                        .max_by_key(|&k| prev_dynamic_region.styled_at[styled[k]]),
+                }
+            } else if self.styles[i].is_none() && policy.split == SplitPolicy::Inherit {
+                // The previous region this one lies in, which it was cut out of.
+                let area = self.area(i).abs();
+                (0..styled.len())
+                    .filter(|&k| inside(k, area))
+                    .max_by(|&a, &b| overlap[a].total_cmp(&overlap[b]))
             } else {
-                curr_angle
+                None
             };
+            if let Some(k) = from {
+                self.styles[i] = prev_dynamic_region.styles[styled[k]].clone();
+                self.styled_at[i] = prev_dynamic_region.styled_at[styled[k]];
+            }
+        }
+        self
+    }
 
-            println!(
-                "{}, |__ {:.3} {:?}, {:?}",
-                curr_idx,
-                curr_angle.to_degrees(),
-                curr_flow,
-                curr_segment.end()
-            );
-
-            let mut min_angle = 8.;
-            let mut best_next = None;
+    pub fn apply_style(&mut self, style: Option<Style>, position: Point) {
+        let stamp = self.styled_at.iter().max().map_or(1, |last| last + 1);
+        for i in 0..self.paths.len() {
+            if self.region_path(i).contains(position) {
+                self.styles[i] = style.clone();
+                self.styled_at[i] = stamp;
+            }
+        }
+    }
+}
 
-            for next_idx in 0..intersect_data.segments.len() {
-                if next_idx == curr_idx {
-                    continue;
-                }
-                let next_segment = intersect_data.segments[next_idx];
+/// Fills of regions drawn into textures, kept by [`DynamicRegions::render`] from one frame to the
+/// next as long as the region they were drawn for is still there.
+#[cfg(feature = "macroquad")]
+#[derive(Default)]
+pub struct FillCache {
+    textures: Vec<FillTexture>,
+}
 
-                let next_flow =
-                    if curr_segment.end().distance(next_segment.start()) < MIN_SEPARATION {
-                        Flow::StartToEnd
-                    } else if curr_segment.end().distance(next_segment.end()) < MIN_SEPARATION {
-                        Flow::EndToStart
-                    } else {
-                        continue;
-                    };
+#[cfg(feature = "macroquad")]
+struct FillTexture {
+    path: BezPath,
+    style: Style,
+    /// Canvas position of the top left corner of the texture.
+    origin: Point,
+    texture: Texture2D,
+    /// Whether the texture was drawn this frame.
+    used: bool,
+}
 
-                let next_segment = match next_flow {
-                    Flow::StartToEnd => next_segment,
-                    Flow::EndToStart => next_segment.reverse(),
-                };
+#[cfg(feature = "macroquad")]
+impl FillCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-                let next_tangent = {
-                    let a = next_segment.eval(0.);
-                    let b = next_segment.eval(0.02);
-                    (b - a).normalize()
-                };
-                let ctn = curr_tangent.normalize();
-                let ntn = next_tangent.normalize();
-                println!(
-                    "\t\t curr_tangent ({:.3}, {:.3}), next_tangent ({:.3}, {:.3})",
-                    ctn.x, ctn.y, ntn.x, ntn.y
-                );
-                let angle = DVec2::new(curr_tangent.x, curr_tangent.y)
-                    .angle_between(DVec2::new(next_tangent.x, next_tangent.y));
-                let angle = if angle.is_sign_negative() {
-                    2. * PI + angle
-                } else {
-                    angle
-                };
-                println!(
-                    "\t\tcnext {}, |__ {:.2}, {:?}, {:?}",
-                    next_idx,
-                    angle.to_degrees(),
-                    next_segment.start(),
-                    next_segment.end()
-                );
-                if angle < min_angle {
-                    min_angle = angle;
-                    best_next = Some((next_idx, next_flow));
-                    println!(
-                        "\tpnext {}, {:?}, {:?}",
-                        next_idx,
-                        next_flow,
-                        next_segment.start()
-                    );
-                }
+    /// Texture of `path` filled with `style`, drawn now unless an earlier frame already did.
+    fn texture(
+        &mut self,
+        path: BezPath,
+        style: &Style,
+        properties: impl FnOnce() -> RegionProperties,
+    ) -> &FillTexture {
+        let index = match self
+            .textures
+            .iter()
+            .position(|texture| texture.path == path && texture.style == *style)
+        {
+            Some(index) => index,
+            None => {
+                let properties = properties();
+                let bbox = properties.bounding_box;
+                let origin = Point::new(bbox.x0.floor(), bbox.y0.floor());
+                let width = (bbox.x1.ceil() - origin.x).max(1.) as usize;
+                let height = (bbox.y1.ceil() - origin.y).max(1.) as usize;
+
+                let mut canvas = Canvas::new(width, height, BLANK);
+                let local = Affine::translate(-origin.to_vec2()) * &path;
+                canvas.fill_path_with(&local, FillRule::NonZero, |point| {
+                    style.color_at(point + origin.to_vec2(), &properties)
+                });
+                let texture =
+                    Texture2D::from_rgba8(width as u16, height as u16, &canvas.to_rgba8());
+                texture.set_filter(FilterMode::Nearest);
+                self.textures.push(FillTexture {
+                    path,
+                    style: style.clone(),
+                    origin,
+                    texture,
+                    used: false,
+                });
+                self.textures.len() - 1
             }
+        };
+        let texture = &mut self.textures[index];
+        texture.used = true;
+        texture
+    }
+}
 
-            if let Some((next_idx, next_flow)) = best_next {
-                curr_idx = next_idx;
-                curr_flow = next_flow;
-            } else {
-                // this should not happen because a segment is connected to it by itself. it will just be different flow.
-                curr_flow = match curr_flow {
-                    Flow::StartToEnd => Flow::EndToStart,
-                    Flow::EndToStart => Flow::StartToEnd,
-                };
-            }
+#[cfg(test)]
+mod tests {
+    use kurbo::{CubicBez, DEFAULT_ACCURACY};
 
-            if curr_idx == start && curr_flow == flow {
-                break;
-            }
-            // if i > 10 {
-            //     break;
-            // }
-            // i += 1;
-        }
+    use super::*;
+    use crate::{
+        color::{BLACK, WHITE},
+        mesh::fixtures::{square, square_with_diagonal},
+    };
 
-        println!(
-            "====================================================> {}, {:?}",
-            curr_idx, curr_flow
-        );
+    /// Regions enclosed by the drawing, as the editor shows them.
+    fn enclosed(mesh: &MMesh, tolerances: &Tolerances) -> DynamicRegions {
+        DynamicRegions::build(intersection(mesh, tolerances), tolerances).filter_outer_regions()
+    }
 
-        if structure.flow.len() > 1 && curr_idx == start && curr_flow == flow {
-            Some((path, structure, visited_index))
-        } else {
-            None
+    fn crossing_mesh() -> MMesh {
+        let mut mesh = MMesh::empty();
+        let corners = [(100., 100.), (300., 100.), (300., 300.), (100., 300.)]
+            .map(|(x, y)| mesh.append_point(Point::new(x, y)));
+        for i in 0..corners.len() {
+            mesh.append_segment(corners[i], None, None, corners[(i + 1) % corners.len()]);
         }
+        let a = mesh.append_point(Point::new(50., 200.));
+        let b = mesh.append_point(Point::new(350., 180.));
+        mesh.append_segment(a, None, None, b);
+        let c = mesh.append_point(Point::new(200., 50.));
+        let d = mesh.append_point(Point::new(220., 350.));
+        mesh.append_segment(c, None, None, d);
+        mesh
     }
 
-    pub fn filter_outer_regions(self) -> Self {
-        let mut paths = Vec::new();
-        let mut colors = Vec::new();
-        let mut structures = Vec::new();
+    #[test]
+    fn output_is_deterministic() {
+        let mesh = crossing_mesh();
+        let tolerances = Tolerances::default();
+        let intersect_data = intersection(&mesh, &tolerances);
+        let regions = DynamicRegions::build(intersect_data.clone(), &tolerances);
+
+        for _ in 0..10 {
+            let mesh = mesh.clone();
+            assert_eq!(intersection(&mesh, &tolerances), intersect_data);
+            assert_eq!(
+                DynamicRegions::build(intersection(&mesh, &tolerances), &tolerances),
+                regions
+            );
+        }
+    }
 
-        let bboxes = self
-            .paths
-            .iter()
-            .map(|path| path.bounding_box())
-            .collect::<Vec<Rect>>();
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_serial() {
+        // Curves scattered by a small deterministic generator, so they cross a lot.
+        let mut state = 7_u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as f64 / (1u64 << 31) as f64 * 400.
+        };
+        let mut mesh = MMesh::empty();
+        for _ in 0..25 {
+            let points = [(); 4].map(|_| mesh.append_point(Point::new(next(), next())));
+            mesh.append_segment(points[0], Some(points[1]), Some(points[2]), points[3]);
+        }
 
-        let max_bbox = bboxes
+        let segments = mesh.segments();
+        let parallel = map_indices(segments.len(), |i| (segments[i].id, segments[i].p4));
+        let serial = segments
             .iter()
-            .max_by(|a, b| a.area().abs().partial_cmp(&b.area().abs()).unwrap());
+            .map(|segment| (segment.id, segment.p4))
+            .collect::<Vec<_>>();
+        assert_eq!(parallel, serial);
+
+        // A pool of one thread runs every parallel step in order.
+        let tolerances = Tolerances::default();
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let intersect_data = intersection(&mesh, &tolerances);
+                let regions = DynamicRegions::build(intersect_data.clone(), &tolerances);
+                (intersect_data, regions)
+            })
+        };
+        let (intersect_data, regions) = run(1);
+        assert!(intersect_data.segments.len() > 100);
+        assert_eq!(run(4), (intersect_data, regions));
+    }
 
-        if self.paths.len() < 3 {
-            paths.push(self.paths[0].clone());
-            colors.push(self.colors[0].clone());
-            structures.push(self.structures[0].clone());
-        } else {
-            for i in 0..self.paths.len() {
-                let mxbbox = max_bbox.unwrap();
-                if mxbbox.area() > bboxes[i].area() {
-                    paths.push(self.paths[i].clone());
-                    colors.push(self.colors[i].clone());
-                    structures.push(self.structures[i].clone());
-                }
-            }
-        }
+    #[test]
+    fn overlapping_segments_share_edges() {
+        let mut mesh = MMesh::empty();
+        let a = mesh.append_point(Point::new(0., 0.));
+        let b = mesh.append_point(Point::new(100., 0.));
+        let s1 = mesh.append_segment(a, None, None, b).unwrap();
+        let c = mesh.append_point(Point::new(150., 0.));
+        let d = mesh.append_point(Point::new(50., 0.));
+        let s2 = mesh.append_segment(c, None, None, d).unwrap();
+        // The same stroke twice.
+        let s3 = mesh.append_segment(a, None, None, b).unwrap();
+
+        let tolerances = Tolerances::default();
+        let intersect_data = intersection(&mesh, &tolerances);
+        assert_eq!(intersect_data.segments.len(), 3);
+        let mut all_parents = intersect_data.all_parents.clone();
+        all_parents.iter_mut().for_each(|parents| parents.sort());
+        assert_eq!(all_parents, vec![vec![s1, s3], vec![s1, s2, s3], vec![s2]]);
+    }
+
+    #[test]
+    fn overlapping_segments_still_cross() {
+        // Two stretches of the same looping cubic share its middle, and the parts before and after
+        // it cross where the loop closes.
+        let looping = PathSeg::Cubic(CubicBez::new(
+            (0., 0.),
+            (150., 100.),
+            (-50., 100.),
+            (100., 0.),
+        ));
+        let (s, t) = pathseg_self_intersection(looping).unwrap();
+        let (seg1, seg2) = (looping.subsegment(0.0..0.6), looping.subsegment(0.4..1.0));
+        assert!(s < 0.4 && t > 0.6);
+
+        let (splits, overlap) = split_pair(seg1, seg2, DEFAULT_ACCURACY);
+        assert!(overlap);
+        let crossing = looping.eval(s);
+        assert!(splits.iter().any(|&(t1, t2)| {
+            seg1.eval(t1).distance(crossing) < 1e-3 && seg2.eval(t2).distance(crossing) < 1e-3
+        }));
+    }
+
+    #[test]
+    fn inner_shape_is_a_hole() {
+        let mut mesh = MMesh::empty();
+        square(&mut mesh, (0., 0.), 90.);
+        square(&mut mesh, (30., 30.), 30.);
+
+        let tolerances = Tolerances::default();
+        let regions = DynamicRegions::build(intersection(&mesh, &tolerances), &tolerances);
+        // Outside of the drawing, the outer square and the inner square.
+        assert_eq!(regions.len(), 3);
+        let outer = (0..3).find(|&i| regions.holes(i).len() == 1).unwrap();
+        let inner = regions.children(outer).collect::<Vec<_>>();
+        assert_eq!(inner.len(), 1);
+        assert_eq!(regions.region_path(outer).area().round(), 8100. - 900.);
+
+        let mut regions = regions.filter_outer_regions();
+        assert_eq!(regions.len(), 2);
+        regions.apply_style(Some(Style::solid(BLACK)), Point::new(10., 10.));
+        let mut canvas = Canvas::new(90, 90, WHITE);
+        regions.rasterize(&mut canvas);
+        assert_eq!(canvas.pixel(10, 10), BLACK);
+        assert_eq!(canvas.pixel(45, 45), WHITE);
+    }
+
+    #[test]
+    fn loose_stroke_is_not_a_hole() {
+        let mut mesh = MMesh::empty();
+        square(&mut mesh, (0., 0.), 90.);
+        let points =
+            [(30., 30.), (60., 40.), (40., 60.)].map(|(x, y)| mesh.append_point(Point::new(x, y)));
+        mesh.append_segment(points[0], None, None, points[1]);
+        mesh.append_segment(points[1], None, None, points[2]);
+
+        let tolerances = Tolerances::default();
+        let regions = enclosed(&mesh, &tolerances);
+        assert_eq!(regions.len(), 1);
+        assert!(regions.holes(0).is_empty());
+        assert_eq!(regions.area(0).round(), 8100.);
+    }
+
+    #[test]
+    fn region_properties() {
+        let mut mesh = MMesh::empty();
+        square(&mut mesh, (0., 0.), 90.);
+        square(&mut mesh, (30., 30.), 30.);
+
+        let tolerances = Tolerances::default();
+        let regions = enclosed(&mesh, &tolerances);
+        let ring = (0..regions.len())
+            .find(|&i| !regions.holes(i).is_empty())
+            .unwrap();
+        let properties = regions.properties(ring, 0.1);
+        assert!((properties.area - 7200.).abs() < 1e-6);
+        assert!(properties.centroid.distance(Point::new(45., 45.)) < 1e-6);
+        assert!((properties.perimeter - 480.).abs() < 1e-6);
+        assert_eq!(properties.bounding_box, Rect::new(0., 0., 90., 90.));
+        assert_eq!(properties.orientation, Orientation::Positive);
+    }
 
-        DynamicRegions {
-            paths,
-            colors,
-            structures,
+    #[test]
+    fn separate_shapes_keep_their_regions() {
+        let mut mesh = MMesh::empty();
+        for offset in [0., 200.] {
+            let corners = square(&mut mesh, (offset, 0.), 100.);
+            mesh.append_segment(corners[0], None, None, corners[2]);
         }
+
+        let tolerances = Tolerances::default();
+        let regions = enclosed(&mesh, &tolerances);
+        assert_eq!(regions.len(), 4);
+        assert!(regions.paths.iter().all(|path| path.area() > 0.));
+
+        assert!(enclosed(&MMesh::empty(), &tolerances).is_empty());
     }
 
-    pub fn style(mut self, prev_dynamic_region: DynamicRegions) -> Self {
-        for i in 0..self.paths.len() {
-            let curr_structure = self.structures[i].clone();
-            let structure_match = prev_dynamic_region
-                .structures
+    #[test]
+    fn style_survives_a_touching_stroke() {
+        let (mut mesh, _) = square_with_diagonal(100.);
+
+        let tolerances = Tolerances::default();
+        let build = |mesh: &MMesh| enclosed(mesh, &tolerances);
+        let mut regions = build(&mesh);
+        regions.apply_style(Some(Style::solid(BLACK)), Point::new(70., 30.));
+
+        // A stroke ending on the top edge splits the boundary of the styled region.
+        let a = mesh.append_point(Point::new(50., -50.));
+        let b = mesh.append_point(Point::new(50., 0.));
+        mesh.append_segment(a, None, None, b);
+        let restyled = build(&mesh).style(regions, mesh.lineage(), &tolerances);
+
+        let styled = (0..restyled.len())
+            .filter(|&i| restyled.styles[i].is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(styled.len(), 1);
+        assert!(
+            restyled
+                .region_path(styled[0])
+                .contains(Point::new(70., 30.))
+        );
+    }
+
+    #[test]
+    fn style_follows_replaced_segments() {
+        let mut mesh = MMesh::empty();
+        let corners = square(&mut mesh, (0., 0.), 100.);
+        let diagonal = mesh
+            .append_segment(corners[0], None, None, corners[2])
+            .unwrap();
+
+        let tolerances = Tolerances::default();
+        let build = |mesh: &MMesh| enclosed(mesh, &tolerances);
+        let mut regions = build(&mesh);
+        regions.apply_style(Some(Style::solid(BLACK)), Point::new(70., 30.));
+
+        // Redraw the diagonal the other way round and cut the top edge in two.
+        mesh.replace_segment(diagonal, corners[2], None, None, corners[0]);
+        let top = mesh.segments()[0].id;
+        mesh.split_segment(top, 0.3);
+        let restyled = build(&mesh).style(regions.clone(), mesh.lineage(), &tolerances);
+
+        let styled = (0..restyled.len())
+            .filter(|&i| restyled.styles[i].is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(styled.len(), 1);
+        assert!(
+            restyled
+                .region_path(styled[0])
+                .contains(Point::new(70., 30.))
+        );
+
+        // Without the lineage the new ids share too little of the old boundary.
+        let unrelated = build(&mesh).style(regions, &Lineage::new(), &tolerances);
+        assert!(unrelated.styles.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn split_and_merge_policies() {
+        let mut mesh = MMesh::empty();
+        square(&mut mesh, (0., 0.), 100.);
+
+        let tolerances = Tolerances::default();
+        let build = |mesh: &MMesh| enclosed(mesh, &tolerances);
+        let mut whole = build(&mesh);
+        whole.apply_style(Some(Style::solid(BLACK)), Point::new(50., 50.));
+
+        // Cut the square in two, away from any of its points.
+        let top = mesh.append_point(Point::new(60., -10.));
+        let bottom = mesh.append_point(Point::new(60., 110.));
+        let cut = mesh.append_segment(top, None, None, bottom).unwrap();
+        let inherit = StylePolicy::default().with_split(SplitPolicy::Inherit);
+        let halves = build(&mesh).style_with_policy(whole, mesh.lineage(), &inherit, &tolerances);
+        assert_eq!(halves.len(), 2);
+        assert!(halves.styles.iter().all(Option::is_some));
+
+        // Restyle both halves and take the cut away again.
+        let mut halves = halves;
+        halves.apply_style(Some(Style::solid(WHITE)), Point::new(80., 50.));
+        mesh.remove_segment(cut);
+        let merged = |merge| {
+            let policy = StylePolicy::default().with_merge(merge);
+            let merged = build(&mesh).style_with_policy(
+                halves.clone(),
+                mesh.lineage(),
+                &policy,
+                &tolerances,
+            );
+            assert_eq!(merged.len(), 1);
+            merged.styles[0].clone()
+        };
+        assert_eq!(merged(MergePolicy::Largest), Some(Style::solid(BLACK)));
+        assert_eq!(merged(MergePolicy::MostRecent), Some(Style::solid(WHITE)));
+    }
+
+    #[test]
+    fn gaps_are_bridged() {
+        let mut mesh = MMesh::empty();
+        let mut line = |(x1, y1), (x2, y2)| {
+            let a = mesh.append_point(Point::new(x1, y1));
+            let b = mesh.append_point(Point::new(x2, y2));
+            mesh.append_segment(a, None, None, b);
+        };
+        // A square left open at its top left corner, cut by a stroke stopping short of its bottom.
+        line((0., 0.), (100., 0.));
+        line((100., 0.), (100., 100.));
+        line((100., 100.), (0., 100.));
+        line((0., 100.), (0., 5.));
+        line((50., -20.), (50., 96.));
+
+        assert!(enclosed(&mesh, &Tolerances::default()).is_empty());
+
+        let closed = enclosed(&mesh, &Tolerances::default().with_gap(8.));
+        assert_eq!(closed.len(), 2);
+        let mut areas = (0..2).map(|i| closed.area(i).round()).collect::<Vec<f64>>();
+        areas.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(areas, vec![5000., 5000.]);
+        // Bridges cover no stretch of any stroke and are left out of the boundaries.
+        assert!(closed.structures.iter().all(|structure| {
+            structure
+                .interval
                 .iter()
-                .enumerate()
-                .find(|&(_idx, prev_strut)| curr_structure.match_structure(prev_strut));
+                .all(|interval| interval.start != interval.end)
+        }));
+    }
 
-            if let Some((idx, _structure)) = structure_match {
-                self.colors[i] = prev_dynamic_region.colors[idx];
-            }
+    #[test]
+    fn bridges_stop_at_the_closest_stroke() {
+        let mut mesh = MMesh::empty();
+        let mut line = |(x1, y1), (x2, y2)| {
+            let a = mesh.append_point(Point::new(x1, y1));
+            let b = mesh.append_point(Point::new(x2, y2));
+            mesh.append_segment(a, None, None, b);
+        };
+        // Two loose ends in reach of each other, on either side of a third stroke.
+        line((0., 0.), (100., 0.));
+        line((50., -50.), (50., -3.));
+        line((50., 50.), (50., 3.));
+
+        let tolerances = Tolerances::default().with_gap(8.);
+        let mut intersect_data = intersection(&mesh, &tolerances);
+        intersect_data.close_gaps(&tolerances);
+        let bridges = (0..intersect_data.segments.len())
+            .filter(|&i| intersect_data.bridges[i])
+            .map(|i| intersect_data.segments[i])
+            .collect::<Vec<_>>();
+        assert_eq!(bridges.len(), 2);
+        let middle = Point::new(50., 0.);
+        assert!(
+            bridges
+                .iter()
+                .all(|bridge| bridge.end().distance(middle) < 1e-6)
+        );
+        assert_eq!(intersect_data.segments.len(), 6);
+    }
+
+    #[test]
+    fn ends_of_split_strokes_are_still_bridged() {
+        let mut mesh = MMesh::empty();
+        let mut line = |(x1, y1), (x2, y2)| {
+            let a = mesh.append_point(Point::new(x1, y1));
+            let b = mesh.append_point(Point::new(x2, y2));
+            mesh.append_segment(a, None, None, b);
+        };
+        // The first bridge splits the last stroke, whose loose end is then on the new tail.
+        line((50., 3.), (50., 100.));
+        line((103., -50.), (103., 50.));
+        line((0., 0.), (100., 0.));
+
+        let tolerances = Tolerances::default().with_gap(8.);
+        let mut intersect_data = intersection(&mesh, &tolerances);
+        intersect_data.close_gaps(&tolerances);
+        let bridges = (0..intersect_data.segments.len())
+            .filter(|&i| intersect_data.bridges[i])
+            .map(|i| intersect_data.segments[i])
+            .collect::<Vec<_>>();
+        assert_eq!(bridges.len(), 2);
+        for (from, to) in [((50., 3.), (50., 0.)), ((100., 0.), (103., 0.))] {
+            let (from, to) = (Point::from(from), Point::from(to));
+            assert!(bridges.iter().any(|bridge| {
+                bridge.start().distance(from) < 1e-6 && bridge.end().distance(to) < 1e-6
+            }));
         }
-        self
     }
 
-    pub fn apply_style(&mut self, color: Option<Color>, position: Point) {
-        for i in 0..self.paths.len() {
-            let mut path = self.paths[i].clone();
-            path.close_path();
-            if path.contains(position) {
-                self.colors[i] = color;
+    #[test]
+    fn curve_closes_on_itself() {
+        let mut mesh = MMesh::empty();
+        let points = [(0., 0.), (150., -80.), (150., 120.), (58., -14.)]
+            .map(|(x, y)| mesh.append_point(Point::new(x, y)));
+        mesh.append_segment(points[0], Some(points[1]), Some(points[2]), points[3]);
+
+        assert!(enclosed(&mesh, &Tolerances::default()).is_empty());
+        let closed = enclosed(&mesh, &Tolerances::default().with_gap(8.));
+        assert_eq!(closed.len(), 1);
+        assert!(closed.area(0) > 0.);
+    }
+
+    #[test]
+    fn signature_is_order_aware() {
+        let mut mesh = MMesh::empty();
+        let ids = (0..3)
+            .map(|_| {
+                let a = mesh.append_point(Point::new(0., 0.));
+                let b = mesh.append_point(Point::new(1., 0.));
+                mesh.append_segment(a, None, None, b).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let structure = |pieces: &[(usize, Range<f64>)]| {
+            let mut structure = DynamicRegionStructure::new();
+            for (i, interval) in pieces {
+                let length = interval.end - interval.start;
+                structure.push(ids[*i], Flow::StartToEnd, length, interval.clone());
             }
-        }
+            structure
+        };
+
+        // The first stroke is visited twice, and the faces only differ in which part comes when.
+        let face = structure(&[(0, 0.0..0.3), (1, 0.0..1.0), (0, 0.5..0.8), (2, 0.0..1.0)]);
+        let other = structure(&[(0, 0.5..0.8), (1, 0.0..1.0), (0, 0.0..0.3), (2, 0.0..1.0)]);
+        assert_eq!(face.similarity(&other), 1.);
+        assert!(!face.signature().matches(&other.signature()));
+
+        // Starting elsewhere, or with a piece cut in two, is still the same boundary.
+        let rotated = structure(&[(0, 0.5..0.8), (2, 0.0..1.0), (0, 0.0..0.3), (1, 0.0..1.0)]);
+        assert!(face.signature().matches(&rotated.signature()));
+        let cut = structure(&[
+            (0, 0.0..0.3),
+            (1, 0.0..0.4),
+            (1, 0.4..1.0),
+            (0, 0.5..0.8),
+            (2, 0.0..1.0),
+        ]);
+        assert!(face.signature().matches(&cut.signature()));
+    }
+
+    #[test]
+    fn matching_order_outweighs_similarity() {
+        let mut mesh = MMesh::empty();
+        let ids = (0..3)
+            .map(|_| {
+                let a = mesh.append_point(Point::new(0., 0.));
+                let b = mesh.append_point(Point::new(1., 0.));
+                mesh.append_segment(a, None, None, b).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let regions = |faces: &[&[(usize, Range<f64>)]]| {
+            let mut regions = DynamicRegions::new();
+            for pieces in faces {
+                let mut structure = DynamicRegionStructure::new();
+                for (i, interval) in pieces.iter() {
+                    let length = interval.end - interval.start;
+                    structure.push(ids[*i], Flow::StartToEnd, length, interval.clone());
+                }
+                let path = Rect::new(0., 0., 10., 10.).to_path(0.1);
+                regions.push(path, structure, false);
+            }
+            regions
+        };
+
+        // The first previous region has every piece of the new one but in another order, the
+        // second runs in the same order with one piece a little shorter.
+        let mut prev = regions(&[
+            &[(0, 0.5..0.8), (1, 0.0..1.0), (0, 0.0..0.3), (2, 0.0..1.0)],
+            &[(0, 0.0..0.3), (1, 0.0..1.0), (0, 0.5..0.8), (2, 0.0..0.8)],
+        ]);
+        prev.styles = vec![Some(Style::solid(BLACK)), Some(Style::solid(WHITE))];
+        let face = regions(&[&[(0, 0.0..0.3), (1, 0.0..1.0), (0, 0.5..0.8), (2, 0.0..1.0)]]);
+        let styled = face.style(prev, &Lineage::new(), &Tolerances::default());
+        assert_eq!(styled.styles[0], Some(Style::solid(WHITE)));
+    }
+
+    #[test]
+    fn pairs_below_threshold_do_not_steer_matching() {
+        // X matches A well, Y matches nothing, but X–B plus Y–A scores higher in total.
+        let similarity = vec![vec![0.9, 0.45], vec![0.8, 0.3]];
+        let centroids = [Point::ZERO; 2];
+        let matches = match_regions(&similarity, &centroids, &centroids);
+        assert_eq!(matches, vec![Some(0), None]);
+    }
+
+    #[test]
+    fn cubic_loop_is_a_region() {
+        let mut mesh = MMesh::empty();
+        let p1 = mesh.append_point(Point::new(0., 0.));
+        let p2 = mesh.append_point(Point::new(150., 100.));
+        let p3 = mesh.append_point(Point::new(-50., 100.));
+        let p4 = mesh.append_point(Point::new(100., 0.));
+        mesh.append_segment(p1, Some(p2), Some(p3), p4);
+
+        let tolerances = Tolerances::default();
+        let intersect_data = intersection(&mesh, &tolerances);
+        assert_eq!(intersect_data.segments.len(), 3);
+
+        let regions = DynamicRegions::build(intersect_data, &tolerances);
+        let loops = regions
+            .paths
+            .iter()
+            .zip(regions.structures.iter())
+            .filter(|(path, structure)| path.area() > 0. && structure.parent.len() == 1)
+            .count();
+        assert_eq!(loops, 1);
     }
 }
@@ -1,12 +1,23 @@
 pub mod algo;
+pub mod animation;
+pub mod color;
+pub mod dcel;
+#[cfg(feature = "serde")]
+pub mod document;
 pub mod dynamic;
+pub mod lineage;
 pub mod mesh;
 pub mod next_id;
+pub mod outline;
+#[cfg(feature = "macroquad")]
 pub mod path;
+#[cfg(feature = "macroquad")]
 pub mod pen;
+pub mod raster;
+pub mod strokes;
+pub mod style;
+pub mod tolerance;
 pub mod util;
 
 pub const WIDTH: i32 = 900;
 pub const HEIGHT: i32 = 600;
-
-pub const MIN_SEPARATION: f64 = 1.5;
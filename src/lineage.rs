@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::mesh::SegmentId;
+
+/// How a segment came to be replaced by others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineageKind {
+    /// One segment cut into pieces.
+    Split,
+    /// Several segments refitted into fewer, like [`crate::mesh::MMesh::simplify`] does.
+    Merge,
+    /// A segment deleted and drawn again.
+    Replace,
+}
+
+/// Record of which segments replaced which, one row per `from -> to` edge.
+///
+/// Segment ids are never reused, so following the edges from an id always ends at segments that
+/// were not replaced, which are the ones still in the mesh or deleted without a successor.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "LineageRows", into = "LineageRows")
+)]
+pub struct Lineage {
+    from: Vec<SegmentId>,
+    to: Vec<SegmentId>,
+    kind: Vec<LineageKind>,
+    /// Whether `to` runs the opposite way to `from`.
+    reversed: Vec<bool>,
+    /// Rows of each `from` id, in the order they were recorded.
+    successors: HashMap<SegmentId, Vec<usize>>,
+}
+
+/// The rows of a [`Lineage`] without its index, which is rebuilt on load.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LineageRows {
+    from: Vec<SegmentId>,
+    to: Vec<SegmentId>,
+    kind: Vec<LineageKind>,
+    reversed: Vec<bool>,
+}
+
+#[cfg(feature = "serde")]
+impl From<LineageRows> for Lineage {
+    fn from(rows: LineageRows) -> Self {
+        let mut lineage = Lineage::new();
+        for i in 0..rows.from.len() {
+            lineage.push(rows.from[i], rows.to[i], rows.kind[i], rows.reversed[i]);
+        }
+        lineage
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Lineage> for LineageRows {
+    fn from(lineage: Lineage) -> Self {
+        Self {
+            from: lineage.from,
+            to: lineage.to,
+            kind: lineage.kind,
+            reversed: lineage.reversed,
+        }
+    }
+}
+
+impl Lineage {
+    pub fn new() -> Self {
+        Self {
+            from: Vec::new(),
+            to: Vec::new(),
+            kind: Vec::new(),
+            reversed: Vec::new(),
+            successors: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, from: SegmentId, to: SegmentId, kind: LineageKind, reversed: bool) {
+        self.successors
+            .entry(from)
+            .or_default()
+            .push(self.from.len());
+        self.from.push(from);
+        self.to.push(to);
+        self.kind.push(kind);
+        self.reversed.push(reversed);
+    }
+
+    /// Edge `index` as `(from, to, kind, reversed)`.
+    pub fn edge(&self, index: usize) -> (SegmentId, SegmentId, LineageKind, bool) {
+        (
+            self.from[index],
+            self.to[index],
+            self.kind[index],
+            self.reversed[index],
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.from.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.from.is_empty()
+    }
+
+    /// Segments `id` turned into, with whether each runs opposite to it. A segment that was never
+    /// replaced resolves to itself.
+    pub fn resolve(&self, id: SegmentId) -> Vec<(SegmentId, bool)> {
+        let mut result = Vec::new();
+        let mut stack = vec![(id, false)];
+        while let Some((current, reversed)) = stack.pop() {
+            let Some(successors) = self.successors.get(&current) else {
+                if !result.contains(&(current, reversed)) {
+                    result.push((current, reversed));
+                }
+                continue;
+            };
+            // Pushed in reverse so successors come out in the order they were recorded.
+            for &i in successors.iter().rev() {
+                stack.push((self.to[i], reversed != self.reversed[i]));
+            }
+        }
+        result
+    }
+}
@@ -1,11 +1,32 @@
-use macroquad::color::*;
 use macroquad::prelude::*;
-use mesh::dynamic::DynamicRegions;
+#[cfg(feature = "serde")]
+use mesh::document::Document;
+use mesh::dynamic::{DynamicRegions, FillCache, MergePolicy, SplitPolicy, StylePolicy};
 use mesh::{
-    HEIGHT, WIDTH, dynamic::intersection, mesh::MMesh, path::Path, pen::Pen,
+    HEIGHT, WIDTH,
+    animation::{Animation, Easing},
+    color::{BLACK, BLUE, GRAY, RED, YELLOW},
+    dynamic::intersection,
+    mesh::MMesh,
+    outline::{DEFAULT_OUTLINE_TOLERANCE, StrokeStyle, outline_mesh},
+    path::Path,
+    pen::Pen,
+    strokes::{DynamicStrokes, PieceStyle},
+    style::{Hatch, Style},
+    tolerance::Tolerances,
     util::mouse_position_point,
 };
 
+/// File the drawing is saved to with F5 and loaded from with F9.
+#[cfg(feature = "serde")]
+const DOCUMENT_PATH: &str = "document.json";
+
+/// Widest gap between strokes that is still closed when finding regions to paint.
+const GAP: f64 = 6.;
+
+/// Seconds between the keyframes recorded with N.
+const ANIMATION_STEP: f64 = 1.;
+
 fn conf() -> Conf {
     Conf {
         window_title: "Mesh".to_string(),
@@ -23,15 +44,48 @@ async fn main() {
     let mut pen = Pen::new();
     let mut path = Path::new();
 
+    let tolerances = Tolerances::default().with_gap(GAP);
+    let style_policy = StylePolicy::default()
+        .with_split(SplitPolicy::Inherit)
+        .with_merge(MergePolicy::Largest);
+
     let mut is_pen_active = true;
     // let mut edit_mesh = true;
 
-    let result = mesh.to_bezpath();
     let mut dynamic = DynamicRegions::new();
-    let mut prev_intersection_data = None;
+    let mut fills = FillCache::new();
+    let mut dynamic_strokes = DynamicStrokes::new();
+
+    let mut animation = Animation::new();
+    let mut keyframe_time = 0.;
+    let mut playback: Option<f64> = None;
 
     loop {
         clear_background(WHITE);
+
+        if is_key_pressed(KeyCode::M) {
+            playback = match playback {
+                Some(_) => None,
+                None => Some(0.),
+            };
+        }
+        if let Some(time) = playback {
+            let frame = animation.sample(&mesh, time);
+            dynamic = DynamicRegions::build(intersection(&frame, &tolerances), &tolerances)
+                .style(dynamic.clone(), frame.lineage(), &tolerances)
+                .filter_outer_regions();
+            dynamic.render(&mut fills);
+            frame.draw();
+            let duration = animation.duration().max(ANIMATION_STEP);
+            playback = Some((time + get_frame_time() as f64) % duration);
+            next_frame().await;
+            continue;
+        }
+        if is_key_pressed(KeyCode::N) {
+            animation.insert_mesh_keyframe(&mesh, keyframe_time, Easing::EaseInOut);
+            keyframe_time += ANIMATION_STEP;
+        }
+
         // if edit_mesh {
         //     mesh.draw();
         // } else {
@@ -40,10 +94,10 @@ async fn main() {
         mesh.draw();
 
         if is_pen_active {
-            pen.update(&mut mesh);
+            pen.update(&mut mesh, &tolerances);
             pen.draw(&mesh);
         } else {
-            path.update(&mut mesh);
+            path.update(&mut mesh, &tolerances);
             path.draw(&mesh);
         }
         let mut setcolor = None;
@@ -58,22 +112,89 @@ async fn main() {
         } else if is_key_pressed(KeyCode::C) {
             setcolor = Some((mouse_position_point(), BLACK));
         }
+        let mut setstyle = setcolor.map(|(position, color)| (position, Style::solid(color)));
+        if is_key_pressed(KeyCode::L) {
+            setstyle = Some((
+                mouse_position_point(),
+                Style::horizontal_gradient(BLUE, YELLOW),
+            ));
+        } else if is_key_pressed(KeyCode::K) {
+            setstyle = Some((mouse_position_point(), Style::radial_gradient(YELLOW, RED)));
+        } else if is_key_pressed(KeyCode::X) {
+            let hatch = Hatch::new(BLACK, std::f64::consts::FRAC_PI_4, 8., 2.);
+            setstyle = Some((mouse_position_point(), Style::hatch(hatch)));
+        }
         if is_key_down(KeyCode::D) {
-            println!("Planar Graph");
             // let (new_mesh, parents) = mesh.planar_graph();
             // let (regions, points) = new_mesh.calculate_regions();
             // styles = calculate_and_draw_style(&regions, parents, &points, styles, setcolor);
-            let intersection = intersection(&mesh);
-            prev_intersection_data = Some(intersection.clone());
+            let intersection = intersection(&mesh, &tolerances);
             intersection.draw();
-            let mut regions = DynamicRegions::build(intersection)
-                .style(dynamic.clone())
-                .filter_outer_regions();
-            if let Some((position, color)) = setcolor {
-                regions.apply_style(Some(color), position);
+            let mut strokes = DynamicStrokes::build(&intersection, &tolerances)
+                .style(dynamic_strokes.clone(), mesh.lineage());
+            if is_key_pressed(KeyCode::H) {
+                strokes.apply_style(
+                    PieceStyle::default().hidden(),
+                    mouse_position_point(),
+                    &tolerances,
+                );
+            } else if is_key_pressed(KeyCode::T) {
+                strokes.apply_style(
+                    PieceStyle::default().with_width(6.),
+                    mouse_position_point(),
+                    &tolerances,
+                );
             }
-            regions.render();
+            let mut regions = DynamicRegions::build(intersection, &tolerances)
+                .filter_outer_regions()
+                .style_with_policy(dynamic.clone(), mesh.lineage(), &style_policy, &tolerances);
+            if let Some((position, style)) = setstyle {
+                regions.apply_style(Some(style), position);
+            }
+            regions.render(&mut fills);
+            strokes.draw(2., BLACK);
             dynamic = regions.clone();
+            dynamic_strokes = strokes;
+        }
+        if is_key_pressed(KeyCode::O) {
+            mesh = outline_mesh(&mesh, &StrokeStyle::new(8.), DEFAULT_OUTLINE_TOLERANCE);
+            // The outline is a new mesh whose ids start over, so nothing styled before carries.
+            dynamic = DynamicRegions::new();
+            dynamic_strokes = DynamicStrokes::new();
+            fills = FillCache::new();
+            pen = Pen::new();
+            path = Path::new();
+        }
+        if is_key_pressed(KeyCode::S) {
+            for chain in mesh.chains() {
+                mesh.simplify(&chain, 2.);
+            }
+            pen = Pen::new();
+            path = Path::new();
+        }
+        #[cfg(feature = "serde")]
+        if is_key_pressed(KeyCode::F5)
+            && let Err(err) = Document::new(&mesh, &dynamic).save(DOCUMENT_PATH)
+        {
+            eprintln!("error: {DOCUMENT_PATH}: {err}");
+        }
+        #[cfg(feature = "serde")]
+        if is_key_pressed(KeyCode::F9) {
+            match Document::load(DOCUMENT_PATH) {
+                Ok(document) => {
+                    // Everything else refers to the ids of the mesh being replaced.
+                    dynamic = document.regions(&tolerances);
+                    fills = FillCache::new();
+                    dynamic_strokes = DynamicStrokes::new();
+                    animation = Animation::new();
+                    keyframe_time = 0.;
+                    playback = None;
+                    mesh = document.mesh;
+                    pen = Pen::new();
+                    path = Path::new();
+                }
+                Err(err) => eprintln!("error: {DOCUMENT_PATH}: {err}"),
+            }
         }
         if is_key_pressed(KeyCode::P) {
             // mesh = mesh.planar_graph().0;
@@ -86,12 +207,6 @@ async fn main() {
         // let (x, y) = mouse_position();
         // draw_text(&format!("({:.2}, {:.2})", x, y), x, y, 20., BLACK);
 
-        // if let Some(data) = &prev_intersection_data {
-        //     for i in 0..data.segments.len() {
-        //         let mid = data.segments[i].eval(0.5);
-        //         draw_text(&format!("{}", i), mid.x as f32, mid.y as f32, 18., BLACK);
-        //     }
-        // }
         next_frame().await
     }
 }
@@ -1,18 +1,32 @@
 use std::collections::{HashMap, HashSet};
 
-use kurbo::{BezPath, CubicBez, Line, ParamCurve, PathSeg, Point, QuadBez};
+use kurbo::{
+    BezPath, CubicBez, Line, ParamCurve, ParamCurveNearest, PathEl, PathSeg, Point, QuadBez,
+    fit_to_bezpath_opt,
+};
+#[cfg(feature = "macroquad")]
 use macroquad::prelude::*;
 
-use crate::next_id::NextId;
+use crate::{
+    algo::ChainCurve,
+    dynamic::Direction,
+    lineage::{Lineage, LineageKind},
+    next_id::NextId,
+    tolerance::Tolerances,
+    util::segment_data_to_pathseg,
+};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MMesh {
     points: PointTable,
     segments: SegmentTable,
+    lineage: Lineage,
     next_id: NextId,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointId(usize);
 
 impl PointId {
@@ -43,7 +57,8 @@ impl PointData {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointTable {
     pub id: Vec<PointId>,
     pub position: Vec<Point>,
@@ -59,7 +74,6 @@ impl PointTable {
 
     pub fn data(&self) -> Vec<PointData> {
         (0..self.id.len())
-            .into_iter()
             .map(|idx| PointData::new(PointIndex(idx), self.id[idx], self.position[idx]))
             .collect()
     }
@@ -85,7 +99,6 @@ impl PointTable {
     }
 
     pub fn remove_multiple(&mut self, points: &HashSet<PointId>) {
-        println!("removing multiple points");
         let mut new = Self::new();
         for i in 0..self.id.len() {
             if !points.contains(&self.id[i]) {
@@ -97,7 +110,8 @@ impl PointTable {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SegmentId(usize);
 
 impl SegmentId {
@@ -106,7 +120,7 @@ impl SegmentId {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SegmentIndex(usize);
 
 impl SegmentIndex {
@@ -158,7 +172,8 @@ impl SegmentData {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SegmentTable {
     pub id: Vec<SegmentId>,
     pub p1: Vec<PointId>,
@@ -180,7 +195,6 @@ impl SegmentTable {
 
     pub fn data(&self) -> Vec<SegmentData> {
         (0..self.id.len())
-            .into_iter()
             .map(|idx| {
                 SegmentData::new(
                     SegmentIndex(idx),
@@ -228,21 +242,31 @@ impl SegmentTable {
     }
 }
 
+/// A run of segments joined end to end through anchors shared by exactly two segments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chain {
+    pub segments: Vec<SegmentId>,
+    /// Direction each segment is traversed in when following the chain.
+    pub directions: Vec<Direction>,
+    pub closed: bool,
+}
+
 impl MMesh {
     pub fn empty() -> Self {
         Self {
             points: PointTable::new(),
             segments: SegmentTable::new(),
+            lineage: Lineage::new(),
             next_id: NextId::new(),
         }
     }
 
     pub fn next_point_id(&mut self) -> PointId {
-        PointId(self.next_id.next())
+        PointId(self.next_id.advance())
     }
 
     pub fn next_segment_id(&mut self) -> SegmentId {
-        SegmentId(self.next_id.next())
+        SegmentId(self.next_id.advance())
     }
 
     pub fn points_data(&self) -> HashMap<PointId, PointData> {
@@ -255,6 +279,11 @@ impl MMesh {
             })
     }
 
+    /// Segments in table order.
+    pub fn segments(&self) -> Vec<SegmentData> {
+        self.segments.data()
+    }
+
     pub fn segments_data(&self) -> HashMap<SegmentId, SegmentData> {
         self.segments
             .data()
@@ -315,14 +344,15 @@ impl MMesh {
         Some(id)
     }
 
-    pub fn closest_point(&self, point: Point, max_radius: Option<f64>) -> Option<(PointId, Point)> {
+    /// Closest point within the pick radius of `point`.
+    pub fn closest_point(&self, point: Point, tolerances: &Tolerances) -> Option<(PointId, Point)> {
         self.points.id.iter().zip(self.points.position.iter()).fold(
             None,
             |mut closest_point, (next_point_id, next_point)| {
-                if next_point.distance(point) < max_radius.unwrap_or(5.) {
+                if next_point.distance(point) < tolerances.pick_radius {
                     closest_point = match closest_point {
                         Some((_, closest))
-                            if next_point.distance(closest) < point.distance(closest) =>
+                            if next_point.distance(point) < closest.distance(point) =>
                         {
                             Some((*next_point_id, *next_point))
                         }
@@ -336,6 +366,62 @@ impl MMesh {
         )
     }
 
+    /// Segment passing closest to `point` within the pick radius, with the parameter on it that
+    /// comes closest.
+    pub fn closest_segment(
+        &self,
+        point: Point,
+        tolerances: &Tolerances,
+    ) -> Option<(SegmentId, f64)> {
+        let points = self.points_data();
+        self.segments()
+            .iter()
+            .map(|data| {
+                let nearest = data
+                    .to_path_seg(&points)
+                    .nearest(point, tolerances.accuracy);
+                (data.id, nearest)
+            })
+            .filter(|(_, nearest)| nearest.distance_sq <= tolerances.pick_radius.powi(2))
+            .min_by(|a, b| a.1.distance_sq.total_cmp(&b.1.distance_sq))
+            .map(|(id, nearest)| (id, nearest.t))
+    }
+
+    /// Segment `id` as a cubic moved to pass through `target` at parameter `t`, keeping its ends.
+    /// `t` is kept away from the ends, where the segment cannot bend.
+    pub fn bent_segment(&self, id: SegmentId, t: f64, target: Point) -> Option<CubicBez> {
+        let data = *self.segments_data().get(&id)?;
+        let cubic = match data.to_path_seg(&self.points_data()) {
+            PathSeg::Line(line) => CubicBez::new(
+                line.p0,
+                line.p0.lerp(line.p1, 1. / 3.),
+                line.p0.lerp(line.p1, 2. / 3.),
+                line.p1,
+            ),
+            PathSeg::Quad(quad) => quad.raise(),
+            PathSeg::Cubic(cubic) => cubic,
+        };
+        // Moving both handles by `offset` moves the point at `t` by `3 t (1 - t) offset`.
+        let t = t.clamp(0.1, 0.9);
+        let offset = (target - cubic.eval(t)) / (3. * t * (1. - t));
+        Some(CubicBez::new(
+            cubic.p0,
+            cubic.p1 + offset,
+            cubic.p2 + offset,
+            cubic.p3,
+        ))
+    }
+
+    /// Draws segment `id` again as [`Self::bent_segment`], recording the replacement in the
+    /// lineage.
+    pub fn bend_segment(&mut self, id: SegmentId, t: f64, target: Point) -> Option<SegmentId> {
+        let bent = self.bent_segment(id, t, target)?;
+        let data = *self.segments_data().get(&id)?;
+        let p2 = self.append_point(bent.p1);
+        let p3 = self.append_point(bent.p2);
+        self.replace_segment(id, data.p1, Some(p2), Some(p3), data.p4)
+    }
+
     pub fn set_point(&mut self, point_id: PointId, point_position: Point) {
         if let Some((_, point)) = self
             .points
@@ -426,8 +512,58 @@ impl MMesh {
         }
     }
 
+    /// Which segments replaced which through [`Self::split_segment`], [`Self::replace_segment`]
+    /// and [`Self::simplify`].
+    pub fn lineage(&self) -> &Lineage {
+        &self.lineage
+    }
+
+    /// Cuts a segment in two at parameter `t`, returning the new segments from its start to its
+    /// end.
+    pub fn split_segment(&mut self, id: SegmentId, t: f64) -> Option<(SegmentId, SegmentId)> {
+        let data = *self.segments_data().get(&id)?;
+        let segment = segment_data_to_pathseg(&self.points_data(), data, Direction::StartToEnd);
+        let middle = self.append_point(segment.eval(t));
+
+        let mut halves = Vec::new();
+        for (range, p1, p4) in [(0.0..t, data.p1, middle), (t..1.0, middle, data.p4)] {
+            let (p2, p3) = match segment.subsegment(range).as_path_el() {
+                PathEl::QuadTo(p2, _) => (Some(self.append_point(p2)), None),
+                PathEl::CurveTo(p2, p3, _) => {
+                    (Some(self.append_point(p2)), Some(self.append_point(p3)))
+                }
+                _ => (None, None),
+            };
+            let half = self.append_segment(p1, p2, p3, p4)?;
+            self.lineage.push(id, half, LineageKind::Split, false);
+            halves.push(half);
+        }
+        self.remove_segment(id);
+
+        Some((halves[0], halves[1]))
+    }
+
+    /// Deletes a segment and draws a new one in its place, keeping track that one replaced the
+    /// other.
+    pub fn replace_segment(
+        &mut self,
+        id: SegmentId,
+        p1: PointId,
+        p2: Option<PointId>,
+        p3: Option<PointId>,
+        p4: PointId,
+    ) -> Option<SegmentId> {
+        let old = *self.segments_data().get(&id)?;
+        let new = self.append_segment(p1, p2, p3, p4)?;
+        let reversed = p1 == old.p4 && p4 == old.p1;
+        self.lineage.push(id, new, LineageKind::Replace, reversed);
+        self.remove_segment(id);
+        Some(new)
+    }
+
     pub fn append_bezpath(&mut self, bezpath: &BezPath) {
         let mut last_point_id = None;
+        let mut subpath_start_id = None;
 
         for element in bezpath.elements() {
             match element {
@@ -435,6 +571,7 @@ impl MMesh {
                     let id = self.next_point_id();
                     self.points.push(id, *point);
                     last_point_id = Some(id);
+                    subpath_start_id = Some(id);
                 }
                 kurbo::PathEl::LineTo(p4) => {
                     let p4_id = self.next_point_id();
@@ -477,13 +614,215 @@ impl MMesh {
                     last_point_id = Some(p4_id);
                 }
                 kurbo::PathEl::ClosePath => {
+                    if let (Some(last), Some(start)) = (last_point_id, subpath_start_id) {
+                        self.close_subpath(last, start);
+                    }
                     last_point_id = None;
-                    // TODO: Append multiple paths and close the path.
+                    subpath_start_id = None;
                 }
             };
         }
     }
 
+    /// Connects `last` back to `start`, reusing `start` as the end anchor when both coincide.
+    fn close_subpath(&mut self, last: PointId, start: PointId) {
+        if last == start {
+            return;
+        }
+        let (Some(last_position), Some(start_position)) =
+            (self.get_point(last), self.get_point(start))
+        else {
+            return;
+        };
+
+        if last_position != start_position {
+            let segment_id = self.next_segment_id();
+            self.segments.push(segment_id, last, None, None, start);
+            return;
+        }
+
+        let Some(index) = self.segments.p4.iter().rposition(|&p4| p4 == last) else {
+            return;
+        };
+        self.segments.p4[index] = start;
+        self.points.remove(last);
+    }
+
+    /// Splits the segments into chains that only break at anchors not shared by exactly two
+    /// segments, so every junction or open end terminates a chain.
+    pub fn chains(&self) -> Vec<Chain> {
+        let segments = self.segments.data();
+
+        let mut incident: HashMap<PointId, Vec<usize>> = HashMap::new();
+        for (index, segment) in segments.iter().enumerate() {
+            incident.entry(segment.p1).or_default().push(index);
+            incident.entry(segment.p4).or_default().push(index);
+        }
+        let degree = |point: PointId| incident.get(&point).map_or(0, |s| s.len());
+
+        let mut visited = vec![false; segments.len()];
+        let mut chains = Vec::new();
+
+        let walk = |start: PointId, first: usize, visited: &mut [bool]| {
+            let mut chain = Chain {
+                segments: Vec::new(),
+                directions: Vec::new(),
+                closed: false,
+            };
+            let mut current_point = start;
+            let mut current_segment = Some(first);
+
+            while let Some(index) = current_segment {
+                visited[index] = true;
+                let segment = segments[index];
+                let (direction, next_point) = if segment.p1 == current_point {
+                    (Direction::StartToEnd, segment.p4)
+                } else {
+                    (Direction::EndToStart, segment.p1)
+                };
+                chain.segments.push(segment.id);
+                chain.directions.push(direction);
+                current_point = next_point;
+
+                current_segment = if degree(current_point) == 2 {
+                    incident[&current_point]
+                        .iter()
+                        .copied()
+                        .find(|&next| !visited[next])
+                } else {
+                    None
+                };
+            }
+
+            chain.closed = current_point == start && degree(start) == 2;
+            chain
+        };
+
+        for segment in &segments {
+            for anchor in [segment.p1, segment.p4] {
+                if degree(anchor) == 2 {
+                    continue;
+                }
+                for &index in &incident[&anchor] {
+                    if !visited[index] {
+                        chains.push(walk(anchor, index, &mut visited));
+                    }
+                }
+            }
+        }
+
+        for index in 0..segments.len() {
+            if !visited[index] {
+                chains.push(walk(segments[index].p1, index, &mut visited));
+            }
+        }
+
+        chains
+    }
+
+    pub fn chain_to_bezpath(&self, chain: &Chain) -> BezPath {
+        let points_data = self.points_data();
+        let segments_data = self.segments_data();
+
+        let mut bezpath = BezPath::new();
+        for (id, &direction) in chain.segments.iter().zip(chain.directions.iter()) {
+            let Some(&segment_data) = segments_data.get(id) else {
+                continue;
+            };
+            let segment = segment_data_to_pathseg(&points_data, segment_data, direction);
+            if bezpath.elements().is_empty() {
+                bezpath.move_to(segment.start());
+            }
+            bezpath.push(segment.as_path_el());
+        }
+        if chain.closed {
+            bezpath.close_path();
+        }
+        bezpath
+    }
+
+    /// Refits the chain with as few cubic segments as possible within `tolerance`.
+    ///
+    /// The anchors at both ends of the chain are kept, so junctions with the rest of the mesh stay
+    /// intact, while the interior anchors and handles are replaced. Returns the new segments in chain
+    /// order, or [`None`] if the chain refers to segments that are not in the mesh.
+    pub fn simplify(&mut self, chain: &Chain, tolerance: f64) -> Option<Vec<SegmentId>> {
+        let points_data = self.points_data();
+        let segments_data = self.segments_data();
+
+        let mut segments = Vec::new();
+        for (id, &direction) in chain.segments.iter().zip(chain.directions.iter()) {
+            let segment_data = *segments_data.get(id)?;
+            segments.push(segment_data_to_pathseg(
+                &points_data,
+                segment_data,
+                direction,
+            ));
+        }
+        if segments.is_empty() {
+            return None;
+        }
+
+        let anchor = |id: &SegmentId, direction: Direction, start: bool| {
+            let data = segments_data[id];
+            match (direction, start) {
+                (Direction::StartToEnd, true) | (Direction::EndToStart, false) => data.p1,
+                (Direction::StartToEnd, false) | (Direction::EndToStart, true) => data.p4,
+            }
+        };
+        let first_anchor = anchor(&chain.segments[0], chain.directions[0], true);
+        let last_anchor = anchor(
+            chain.segments.last().unwrap(),
+            *chain.directions.last().unwrap(),
+            false,
+        );
+
+        // Everything that can fail is checked before the mesh is touched, so a chain that cannot
+        // be simplified is left exactly as it was.
+        self.get_point(first_anchor)?;
+        self.get_point(last_anchor)?;
+        let fitted = fit_to_bezpath_opt(&ChainCurve::new(segments), tolerance);
+        let fitted_segments = fitted.segments().count();
+        if fitted_segments == 0 {
+            return None;
+        }
+
+        let mut new_segments = Vec::new();
+        let mut last_point = first_anchor;
+        for (i, segment) in fitted.segments().enumerate() {
+            let p4 = if i + 1 == fitted_segments {
+                last_anchor
+            } else {
+                self.append_point(segment.end())
+            };
+            let (p2, p3) = match segment.as_path_el() {
+                PathEl::QuadTo(p2, _) => (Some(self.append_point(p2)), None),
+                PathEl::CurveTo(p2, p3, _) => {
+                    (Some(self.append_point(p2)), Some(self.append_point(p3)))
+                }
+                _ => (None, None),
+            };
+            let id = self.next_segment_id();
+            self.segments.push(id, last_point, p2, p3, p4);
+            new_segments.push(id);
+            last_point = p4;
+        }
+
+        for (&old, &direction) in chain.segments.iter().zip(chain.directions.iter()) {
+            for &new in &new_segments {
+                let reversed = direction == Direction::EndToStart;
+                self.lineage.push(old, new, LineageKind::Merge, reversed);
+            }
+        }
+
+        // Removing the old segments last keeps the end anchors from being dropped as floating.
+        for id in &chain.segments {
+            self.remove_segment(*id);
+        }
+
+        Some(new_segments)
+    }
+
     pub fn to_bezpath(&self) -> BezPath {
         let mut bezpath = BezPath::new();
         if self.segments.id.is_empty() {
@@ -559,6 +898,7 @@ impl MMesh {
         bezpath
     }
 
+    #[cfg(feature = "macroquad")]
     pub fn draw(&self) {
         for point in &self.points.position {
             draw_circle(point.x as f32, point.y as f32, 3., RED);
@@ -625,6 +965,32 @@ impl MMesh {
     }
 }
 
+/// Drawings shared by the tests of several modules.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    /// Appends the outline of an axis aligned square, returning its corners clockwise from the
+    /// top left.
+    pub fn square(mesh: &mut MMesh, (x, y): (f64, f64), size: f64) -> [PointId; 4] {
+        let corners = [(x, y), (x + size, y), (x + size, y + size), (x, y + size)]
+            .map(|(x, y)| mesh.append_point(Point::new(x, y)));
+        for i in 0..4 {
+            mesh.append_segment(corners[i], None, None, corners[(i + 1) % 4]);
+        }
+        corners
+    }
+
+    /// Square at the origin cut in two triangles by the diagonal from its top left corner, with
+    /// its corners clockwise from the top left.
+    pub fn square_with_diagonal(size: f64) -> (MMesh, [PointId; 4]) {
+        let mut mesh = MMesh::empty();
+        let corners = square(&mut mesh, (0., 0.), size);
+        mesh.append_segment(corners[0], None, None, corners[2]);
+        (mesh, corners)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -643,4 +1009,84 @@ mod tests {
         let result = mesh.to_bezpath();
         assert_eq!(result, bezpath);
     }
+
+    #[test]
+    fn chains_break_at_junctions() {
+        let mut mesh = MMesh::empty();
+        let center = mesh.append_point(Point::new(0., 0.));
+        let a = mesh.append_point(Point::new(10., 0.));
+        let b = mesh.append_point(Point::new(20., 0.));
+        let c = mesh.append_point(Point::new(0., 10.));
+        let d = mesh.append_point(Point::new(-10., 0.));
+
+        let s1 = mesh.append_segment(center, None, None, a).unwrap();
+        let s2 = mesh.append_segment(b, None, None, a).unwrap();
+        let s3 = mesh.append_segment(center, None, None, c).unwrap();
+        let s4 = mesh.append_segment(d, None, None, center).unwrap();
+
+        let chains = mesh.chains();
+        assert_eq!(chains.len(), 3);
+        assert_eq!(chains[0].segments, vec![s1, s2]);
+        assert_eq!(
+            chains[0].directions,
+            vec![Direction::StartToEnd, Direction::EndToStart]
+        );
+        assert!(!chains[0].closed);
+        assert_eq!(chains[1].segments, vec![s3]);
+        assert_eq!(chains[2].segments, vec![s4]);
+    }
+
+    #[test]
+    fn simplify_keeps_chain_anchors() {
+        let mut mesh = MMesh::empty();
+        let mut anchors = Vec::new();
+        for i in 0..=20 {
+            let angle = std::f64::consts::FRAC_PI_2 * i as f64 / 20.;
+            anchors.push(mesh.append_point(Point::new(100. * angle.cos(), 100. * angle.sin())));
+        }
+        for pair in anchors.windows(2) {
+            mesh.append_segment(pair[0], None, None, pair[1]);
+        }
+
+        let chain = mesh.chains().remove(0);
+        let simplified = mesh.simplify(&chain, 0.5).unwrap();
+        assert!(simplified.len() < 4);
+
+        let segments_data = mesh.segments_data();
+        assert_eq!(segments_data.len(), simplified.len());
+        assert_eq!(segments_data[&simplified[0]].p1, anchors[0]);
+        assert_eq!(
+            segments_data[simplified.last().unwrap()].p4,
+            *anchors.last().unwrap()
+        );
+        assert!(mesh.get_point(anchors[10]).is_none());
+
+        let merged = mesh.lineage().resolve(chain.segments[5]);
+        let expected = simplified.iter().map(|&id| (id, false)).collect::<Vec<_>>();
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn bending_replaces_the_segment() {
+        let mut mesh = MMesh::empty();
+        let a = mesh.append_point(Point::new(0., 0.));
+        let b = mesh.append_point(Point::new(100., 0.));
+        let line = mesh.append_segment(a, None, None, b).unwrap();
+
+        let tolerances = Tolerances::default();
+        let (id, t) = mesh
+            .closest_segment(Point::new(25., 2.), &tolerances)
+            .unwrap();
+        assert_eq!(id, line);
+        assert!((t - 0.25).abs() < 1e-6);
+        assert!(
+            mesh.closest_segment(Point::new(25., 20.), &tolerances)
+                .is_none()
+        );
+
+        let bent = mesh.bend_segment(line, 0.5, Point::new(50., 30.)).unwrap();
+        let segment = mesh.segments_data()[&bent].to_path_seg(&mesh.points_data());
+        assert!(segment.eval(0.5).distance(Point::new(50., 30.)) < 1e-9);
+        assert_eq!(mesh.lineage().resolve(line), vec![(bent, false)]);
+    }
 }
@@ -1,4 +1,5 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NextId(usize);
 
 impl NextId {
@@ -6,7 +7,7 @@ impl NextId {
         Self(0)
     }
 
-    pub fn next(&mut self) -> usize {
+    pub fn advance(&mut self) -> usize {
         self.0 += 1;
         self.0 - 1
     }
@@ -0,0 +1,109 @@
+use kurbo::{BezPath, Stroke, StrokeOpts};
+
+use crate::mesh::MMesh;
+
+pub use kurbo::{Cap, Join};
+
+/// Accuracy used to approximate the offset curves when none is given.
+pub const DEFAULT_OUTLINE_TOLERANCE: f64 = 0.1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f64,
+    pub join: Join,
+    pub cap: Cap,
+    pub miter_limit: f64,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f64) -> Self {
+        Self {
+            width,
+            join: Join::Miter,
+            cap: Cap::Butt,
+            miter_limit: 4.,
+        }
+    }
+
+    pub fn with_join(mut self, join: Join) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: Cap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f64) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+
+    fn to_kurbo(&self) -> Stroke {
+        Stroke::new(self.width)
+            .with_join(self.join)
+            .with_caps(self.cap)
+            .with_miter_limit(self.miter_limit)
+    }
+}
+
+pub fn outline_path(path: &BezPath, style: &StrokeStyle, tolerance: f64) -> BezPath {
+    kurbo::stroke(
+        path.iter(),
+        &style.to_kurbo(),
+        &StrokeOpts::default(),
+        tolerance,
+    )
+}
+
+/// Outline of every stroke in the mesh as closed, filled geometry.
+///
+/// Segments are stroked chain by chain so anchors shared by two segments get a proper join,
+/// while junctions and open ends get caps.
+pub fn outline(mesh: &MMesh, style: &StrokeStyle, tolerance: f64) -> BezPath {
+    let mut result = BezPath::new();
+    for chain in mesh.chains() {
+        let path = mesh.chain_to_bezpath(&chain);
+        result.extend(outline_path(&path, style, tolerance));
+    }
+    result
+}
+
+/// Builds a new mesh out of the stroke outlines so they can take part in region detection.
+pub fn outline_mesh(mesh: &MMesh, style: &StrokeStyle, tolerance: f64) -> MMesh {
+    let mut outline_mesh = MMesh::empty();
+    outline_mesh.append_bezpath(&outline(mesh, style, tolerance));
+    outline_mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::{Point, Shape};
+
+    use super::*;
+
+    #[test]
+    fn outline_of_line_is_a_rectangle() {
+        let mut mesh = MMesh::empty();
+        let p1 = mesh.append_point(Point::new(0., 0.));
+        let p4 = mesh.append_point(Point::new(100., 0.));
+        mesh.append_segment(p1, None, None, p4);
+
+        let style = StrokeStyle::new(10.);
+        let path = outline(&mesh, &style, DEFAULT_OUTLINE_TOLERANCE);
+        assert!((path.area().abs() - 1000.).abs() < 1e-6);
+
+        let bbox = path.bounding_box();
+        assert!((bbox.y0 + 5.).abs() < 1e-6 && (bbox.y1 - 5.).abs() < 1e-6);
+
+        let style = style.with_cap(Cap::Square);
+        let path = outline(&mesh, &style, DEFAULT_OUTLINE_TOLERANCE);
+        assert!((path.area().abs() - 1100.).abs() < 1e-6);
+
+        let outline = outline_mesh(&mesh, &style, DEFAULT_OUTLINE_TOLERANCE);
+        let chains = outline.chains();
+        assert_eq!(chains.len(), 1);
+        assert!(chains[0].closed);
+    }
+}
@@ -1,38 +1,49 @@
 use macroquad::prelude::*;
 
 use crate::{
-    mesh::{MMesh, PointId},
-    util::mouse_position_point,
+    mesh::{MMesh, PointId, SegmentId},
+    tolerance::Tolerances,
+    util::{draw_bez, mouse_position_point},
 };
 
 pub enum State {
     Idle(Option<PointId>),
     Drag(PointId),
+    /// Bending a segment by the point at the given parameter, redrawn once released.
+    Bend(SegmentId, f64),
 }
 
 pub struct Path {
     state: State,
 }
 
+impl Default for Path {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Path {
     pub fn new() -> Self {
         Self {
             state: State::Idle(None),
         }
     }
-    pub fn update(&mut self, mesh: &mut MMesh) {
+    pub fn update(&mut self, mesh: &mut MMesh, tolerances: &Tolerances) {
         match &mut self.state {
             State::Idle(point_id) => {
                 let mouse_position = mouse_position_point();
                 if is_mouse_button_pressed(MouseButton::Left) {
                     *point_id = mesh
-                        .closest_point(mouse_position, Some(3.))
+                        .closest_point(mouse_position, tolerances)
                         .map(|(id, _)| id);
 
-                    println!("point id {:?}", point_id);
-
                     if let Some(point_id) = *point_id {
                         self.state = State::Drag(point_id);
+                    } else if let Some((segment, t)) =
+                        mesh.closest_segment(mouse_position, tolerances)
+                    {
+                        self.state = State::Bend(segment, t);
                     }
                 }
             }
@@ -44,6 +55,12 @@ impl Path {
                     mesh.set_point(*point_id, mouse_position);
                 }
             }
+            State::Bend(segment, t) => {
+                if is_mouse_button_released(MouseButton::Left) {
+                    mesh.bend_segment(*segment, *t, mouse_position_point());
+                    self.state = State::Idle(None);
+                }
+            }
         }
     }
 
@@ -59,6 +76,11 @@ impl Path {
                     draw_circle(point.x as f32, point.y as f32, 3., SKYBLUE);
                 }
             }
+            State::Bend(segment, t) => {
+                if let Some(bent) = mesh.bent_segment(segment, t, mouse_position_point()) {
+                    draw_bez(bent);
+                }
+            }
         }
     }
 }
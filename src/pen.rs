@@ -3,12 +3,27 @@ use macroquad::prelude::*;
 
 use crate::{
     mesh::{MMesh, PointId},
+    tolerance::Tolerances,
     util::{
         draw_bez, dvec2_to_point, mouse_position_dvec2, mouse_position_point, point_to_dvec2,
         xdraw_circle, xdraw_line,
     },
 };
 
+/// Point to start or end a stroke at: an existing point under `position`, a new one splitting the
+/// segment under it so the strokes join, or a new free point.
+fn pick_point(mesh: &mut MMesh, position: Point, tolerances: &Tolerances) -> PointId {
+    if let Some((id, _)) = mesh.closest_point(position, tolerances) {
+        return id;
+    }
+    if let Some((segment, t)) = mesh.closest_segment(position, tolerances)
+        && let Some((first, _)) = mesh.split_segment(segment, t)
+    {
+        return mesh.segments_data()[&first].p4;
+    }
+    mesh.append_point(position)
+}
+
 #[derive(Clone, Copy, Debug)]
 enum State {
     Idle,
@@ -22,12 +37,18 @@ pub struct Pen {
     state: State,
 }
 
+impl Default for Pen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Pen {
     pub fn new() -> Pen {
         Pen { state: State::Idle }
     }
 
-    pub fn update(&mut self, mesh: &mut MMesh) {
+    pub fn update(&mut self, mesh: &mut MMesh, tolerances: &Tolerances) {
         match &mut self.state {
             State::Idle => {
                 if is_mouse_button_pressed(MouseButton::Left) {
@@ -35,11 +56,7 @@ impl Pen {
                     let (x, y) = mouse_position();
                     let mouse_position = Point::new(x as f64, y as f64);
 
-                    let point_id = mesh
-                        .closest_point(mouse_position, Some(3.))
-                        .map(|(id, _)| id)
-                        .or_else(|| Some(mesh.append_point(mouse_position)))
-                        .unwrap();
+                    let point_id = pick_point(mesh, mouse_position, tolerances);
 
                     // transition to the drag state
                     self.state = State::DragStartPoint(point_id, None);
@@ -55,10 +72,9 @@ impl Pen {
                     // Get mouse position
                     let mouse_position = mouse_position_point();
                     // Calculate handle position
-                    *p2 = if mesh
-                        .get_point(*p1)
-                        .is_some_and(|point| point.distance(mouse_position) > 3.)
-                    {
+                    *p2 = if mesh.get_point(*p1).is_some_and(|point| {
+                        point.distance(mouse_position) > tolerances.pick_radius
+                    }) {
                         Some(mouse_position)
                     } else {
                         None
@@ -73,15 +89,13 @@ impl Pen {
                     mesh.remove_floating_point(*p1);
                     self.state = State::Idle;
                 } else if is_mouse_button_pressed(MouseButton::Left) {
-                    if mesh.get_point(*p1).unwrap().distance(mouse_position) < 5. {
+                    if mesh.get_point(*p1).unwrap().distance(mouse_position)
+                        < tolerances.pick_radius
+                    {
                         self.state = State::DragStartPoint(*p1, Some(mouse_position));
                     } else {
                         // Create a new endpoint or connect to existing one.
-                        let p4 = mesh
-                            .closest_point(mouse_position, Some(3.))
-                            .map(|(id, _)| id)
-                            .or_else(|| Some(mesh.append_point(mouse_position)))
-                            .unwrap();
+                        let p4 = pick_point(mesh, mouse_position, tolerances);
 
                         // Transition to the drag state
                         self.state = State::DragSecondPoint(*p1, *p2, None, p4);
@@ -108,7 +122,7 @@ impl Pen {
                     let mouse_position = mouse_position_dvec2();
                     // Calculate handle position
                     let p4_pos = point_to_dvec2(mesh.get_point(*p4).unwrap());
-                    *p3 = if p4_pos.distance(mouse_position) > 3. {
+                    *p3 = if p4_pos.distance(mouse_position) > tolerances.pick_radius {
                         let p3 = 2. * p4_pos - mouse_position;
                         Some(dvec2_to_point(p3))
                     } else {
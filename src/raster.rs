@@ -0,0 +1,334 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use crate::{
+    color::Color,
+    mesh::MMesh,
+    outline::{DEFAULT_OUTLINE_TOLERANCE, StrokeStyle, outline, outline_path},
+};
+use kurbo::{BezPath, Line, PathEl, Point};
+
+/// Flattening tolerance used when turning curves into edges, in pixels.
+pub const FLATTEN_TOLERANCE: f64 = 0.05;
+
+/// Sub-scanlines sampled per pixel row for vertical anti-aliasing.
+const SAMPLES_PER_ROW: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+/// Flattens `path` into straight edges, implicitly closing every subpath as filling does.
+pub fn flatten_edges(path: &BezPath, tolerance: f64) -> Vec<Line> {
+    let mut edges = Vec::new();
+    let mut start = None;
+    let mut last = Point::ZERO;
+
+    kurbo::flatten(path, tolerance, |element| match element {
+        PathEl::MoveTo(point) => {
+            if let Some(start) = start.replace(point) {
+                edges.push(Line::new(last, start));
+            }
+            last = point;
+        }
+        PathEl::LineTo(point) => {
+            edges.push(Line::new(last, point));
+            last = point;
+        }
+        PathEl::ClosePath => {
+            if let Some(start) = start.take() {
+                edges.push(Line::new(last, start));
+                last = start;
+            }
+        }
+        _ => unreachable!("flatten only emits lines"),
+    });
+    if let Some(start) = start {
+        edges.push(Line::new(last, start));
+    }
+
+    edges.retain(|edge| edge.p0.y != edge.p1.y);
+    edges
+}
+
+/// Horizontal spans that are inside the edges at height `y` according to `fill_rule`.
+pub fn scanline_spans(edges: &[Line], y: f64, fill_rule: FillRule) -> Vec<(f64, f64)> {
+    let mut crossings = edges
+        .iter()
+        .filter_map(|edge| {
+            let (top, bottom, winding) = if edge.p0.y < edge.p1.y {
+                (edge.p0, edge.p1, 1)
+            } else {
+                (edge.p1, edge.p0, -1)
+            };
+            if y < top.y || y >= bottom.y {
+                return None;
+            }
+            let t = (y - top.y) / (bottom.y - top.y);
+            Some((top.x + t * (bottom.x - top.x), winding))
+        })
+        .collect::<Vec<(f64, i32)>>();
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut spans = Vec::new();
+    let mut winding = 0;
+    let mut span_start = 0.;
+    for (x, direction) in crossings {
+        let was_inside = is_inside(winding, fill_rule);
+        winding += direction;
+        let inside = is_inside(winding, fill_rule);
+        if !was_inside && inside {
+            span_start = x;
+        } else if was_inside && !inside && x > span_start {
+            spans.push((span_start, x));
+        }
+    }
+    spans
+}
+
+fn is_inside(winding: i32, fill_rule: FillRule) -> bool {
+    match fill_rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    }
+}
+
+/// CPU render target holding premultiplied RGBA pixels.
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        let pixel = premultiply(background, 1.);
+        Self {
+            width,
+            height,
+            pixels: vec![pixel; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let [r, g, b, a] = self.pixels[y * self.width + x];
+        if a == 0. {
+            Color::new(0., 0., 0., 0.)
+        } else {
+            Color::new(r / a, g / a, b / a, a)
+        }
+    }
+
+    pub fn fill_path(&mut self, path: &BezPath, color: Color, fill_rule: FillRule) {
+        self.fill_path_with(path, fill_rule, |_| color);
+    }
+
+    /// Fills `path` with the colour `paint` gives at the centre of each pixel.
+    pub fn fill_path_with(
+        &mut self,
+        path: &BezPath,
+        fill_rule: FillRule,
+        paint: impl Fn(Point) -> Color,
+    ) {
+        let edges = flatten_edges(path, FLATTEN_TOLERANCE);
+        if edges.is_empty() {
+            return;
+        }
+
+        let (y_min, y_max) = edges.iter().fold((f64::MAX, f64::MIN), |(min, max), edge| {
+            (
+                min.min(edge.p0.y.min(edge.p1.y)),
+                max.max(edge.p0.y.max(edge.p1.y)),
+            )
+        });
+        let first_row = y_min.floor().max(0.) as usize;
+        let last_row = (y_max.ceil().max(0.) as usize).min(self.height);
+
+        let mut coverage = vec![0f32; self.width];
+        for row in first_row..last_row {
+            coverage.iter_mut().for_each(|c| *c = 0.);
+
+            for sample in 0..SAMPLES_PER_ROW {
+                let y = row as f64 + (sample as f64 + 0.5) / SAMPLES_PER_ROW as f64;
+                for (x0, x1) in scanline_spans(&edges, y, fill_rule) {
+                    self.accumulate_span(&mut coverage, x0, x1);
+                }
+            }
+
+            for (x, &c) in coverage.iter().enumerate() {
+                if c > 0. {
+                    let color = paint(Point::new(x as f64 + 0.5, row as f64 + 0.5));
+                    self.blend(x, row, color, c.min(1.));
+                }
+            }
+        }
+    }
+
+    /// Adds the horizontal coverage of one sub-scanline span to the row accumulator.
+    fn accumulate_span(&self, coverage: &mut [f32], x0: f64, x1: f64) {
+        let weight = 1. / SAMPLES_PER_ROW as f32;
+        let x0 = x0.clamp(0., self.width as f64);
+        let x1 = x1.clamp(0., self.width as f64);
+        if x1 <= x0 {
+            return;
+        }
+
+        let first = x0.floor() as usize;
+        let last = (x1.ceil() as usize).min(self.width);
+        for (x, c) in coverage.iter_mut().enumerate().take(last).skip(first) {
+            let left = x0.max(x as f64);
+            let right = x1.min(x as f64 + 1.);
+            *c += (right - left).max(0.) as f32 * weight;
+        }
+    }
+
+    fn blend(&mut self, x: usize, y: usize, color: Color, coverage: f32) {
+        let source = premultiply(color, coverage);
+        let pixel = &mut self.pixels[y * self.width + x];
+        let inverse = 1. - source[3];
+        for channel in 0..4 {
+            pixel[channel] = source[channel] + pixel[channel] * inverse;
+        }
+    }
+
+    pub fn stroke_path(&mut self, path: &BezPath, style: &StrokeStyle, color: Color) {
+        let outline = outline_path(path, style, DEFAULT_OUTLINE_TOLERANCE);
+        self.fill_path(&outline, color, FillRule::NonZero);
+    }
+
+    pub fn stroke_mesh(&mut self, mesh: &MMesh, style: &StrokeStyle, color: Color) {
+        let outline = outline(mesh, style, DEFAULT_OUTLINE_TOLERANCE);
+        self.fill_path(&outline, color, FillRule::NonZero);
+    }
+
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = self.pixel(x, y);
+                for channel in [color.r, color.g, color.b, color.a] {
+                    bytes.push((channel.clamp(0., 1.) * 255.).round() as u8);
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+        let file = File::create(path)?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba8())?;
+        Ok(())
+    }
+}
+
+fn premultiply(color: Color, coverage: f32) -> [f32; 4] {
+    let alpha = color.a * coverage;
+    [color.r * alpha, color.g * alpha, color.b * alpha, alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, RED, WHITE};
+    use kurbo::{Rect, Shape};
+
+    #[test]
+    fn fill_is_anti_aliased() {
+        let mut canvas = Canvas::new(20, 20, WHITE);
+        let square = Rect::new(5.5, 5., 15.5, 15.).to_path(0.1);
+        canvas.fill_path(&square, BLACK, FillRule::NonZero);
+
+        assert_eq!(canvas.pixel(10, 10), BLACK);
+        assert_eq!(canvas.pixel(2, 10), WHITE);
+
+        let edge = canvas.pixel(5, 10);
+        assert!((edge.r - 0.5).abs() < 1e-3);
+        assert!((edge.a - 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn even_odd_leaves_holes() {
+        let mut ring = Rect::new(0., 0., 20., 20.).to_path(0.1);
+        ring.extend(Rect::new(5., 5., 15., 15.).to_path(0.1));
+
+        let mut canvas = Canvas::new(20, 20, WHITE);
+        canvas.fill_path(&ring, RED, FillRule::EvenOdd);
+        assert_eq!(canvas.pixel(2, 2), RED);
+        assert_eq!(canvas.pixel(10, 10), WHITE);
+
+        let mut canvas = Canvas::new(20, 20, WHITE);
+        canvas.fill_path(&ring, RED, FillRule::NonZero);
+        assert_eq!(canvas.pixel(10, 10), RED);
+    }
+
+    #[test]
+    fn spans_of_an_l_shape() {
+        let mut l_shape = BezPath::new();
+        l_shape.move_to((0., 0.));
+        l_shape.line_to((10., 0.));
+        l_shape.line_to((10., 20.));
+        l_shape.line_to((30., 20.));
+        l_shape.line_to((30., 30.));
+        l_shape.line_to((0., 30.));
+        l_shape.close_path();
+        let edges = flatten_edges(&l_shape, 0.1);
+
+        assert_eq!(
+            scanline_spans(&edges, 10., FillRule::NonZero),
+            vec![(0., 10.)]
+        );
+        assert_eq!(
+            scanline_spans(&edges, 25., FillRule::NonZero),
+            vec![(0., 30.)]
+        );
+        assert!(scanline_spans(&edges, 35., FillRule::NonZero).is_empty());
+    }
+
+    #[test]
+    fn spans_of_a_ring() {
+        let mut ring = Rect::new(0., 0., 30., 30.).to_path(0.1);
+        ring.extend(Rect::new(10., 10., 20., 20.).to_path(0.1));
+        let edges = flatten_edges(&ring, 0.1);
+
+        assert_eq!(
+            scanline_spans(&edges, 15., FillRule::EvenOdd),
+            vec![(0., 10.), (20., 30.)]
+        );
+        assert_eq!(
+            scanline_spans(&edges, 15., FillRule::NonZero),
+            vec![(0., 30.)]
+        );
+        assert_eq!(
+            scanline_spans(&edges, 5., FillRule::EvenOdd),
+            vec![(0., 30.)]
+        );
+
+        // With the inner square running the other way the hole shows under both rules.
+        let mut ring = Rect::new(0., 0., 30., 30.).to_path(0.1);
+        ring.extend(
+            Rect::new(10., 10., 20., 20.)
+                .to_path(0.1)
+                .reverse_subpaths(),
+        );
+        let edges = flatten_edges(&ring, 0.1);
+        assert_eq!(
+            scanline_spans(&edges, 15., FillRule::NonZero),
+            vec![(0., 10.), (20., 30.)]
+        );
+    }
+}
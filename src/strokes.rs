@@ -0,0 +1,331 @@
+use std::ops::Range;
+
+use kurbo::{BezPath, ParamCurveNearest, PathSeg, Point};
+
+#[cfg(feature = "macroquad")]
+use crate::util::xdraw_line;
+use crate::{
+    color::Color,
+    dcel::{Dcel, VertexId},
+    dynamic::IntersectData,
+    lineage::Lineage,
+    mesh::SegmentId,
+    outline::StrokeStyle,
+    raster::Canvas,
+    tolerance::Tolerances,
+};
+#[cfg(feature = "macroquad")]
+use kurbo::ParamCurve;
+
+/// Style of one stroke piece, unset fields fall back to the style of the whole drawing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PieceStyle {
+    pub color: Option<Color>,
+    pub width: Option<f64>,
+    pub hidden: bool,
+}
+
+impl PieceStyle {
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+}
+
+/// What identifies a stroke piece across edits: the segment it is part of, where on it, and the
+/// segments crossing it at either end.
+#[derive(Clone, Debug, PartialEq)]
+struct PieceIdentity {
+    parent: SegmentId,
+    interval: Range<f64>,
+    /// Parents of the other pieces meeting this one at its start, sorted.
+    start: Vec<SegmentId>,
+    /// Parents of the other pieces meeting this one at its end, sorted.
+    end: Vec<SegmentId>,
+}
+
+impl PieceIdentity {
+    /// Same identity in terms of the segments its segments turned into, with the ends swapped if
+    /// its parent was redrawn the other way round.
+    fn resolve(&self, lineage: &Lineage) -> Vec<PieceIdentity> {
+        let resolve_all = |ids: &[SegmentId]| {
+            let mut resolved = ids
+                .iter()
+                .flat_map(|&id| lineage.resolve(id))
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            resolved.sort();
+            resolved.dedup();
+            resolved
+        };
+        let (start, end) = (resolve_all(&self.start), resolve_all(&self.end));
+
+        lineage
+            .resolve(self.parent)
+            .into_iter()
+            .map(|(parent, reversed)| {
+                let interval = if parent == self.parent {
+                    self.interval.clone()
+                } else {
+                    0.0..1.0
+                };
+                let (start, end) = if reversed {
+                    (end.clone(), start.clone())
+                } else {
+                    (start.clone(), end.clone())
+                };
+                PieceIdentity {
+                    parent,
+                    interval,
+                    start,
+                    end,
+                }
+            })
+            .collect()
+    }
+
+    /// How well `other` identifies the same piece: the share of the interval they have in common,
+    /// then as a tie-break how many ends are crossed by the same segments. [`None`] if they are
+    /// on different segments, or on different parts of the same one.
+    ///
+    /// A piece whose parent was replaced resolves to the whole new parent, so every piece of it
+    /// shares all its interval and the ends alone decide.
+    fn score(&self, other: &PieceIdentity) -> Option<(f64, usize)> {
+        if self.parent != other.parent {
+            return None;
+        }
+        let overlap = self.interval.end.min(other.interval.end)
+            - self.interval.start.max(other.interval.start);
+        let share = overlap / (self.interval.end - self.interval.start).max(f64::EPSILON);
+        let ends = (self.start == other.start) as usize + (self.end == other.end) as usize;
+        (overlap > 0.).then_some((share, ends))
+    }
+}
+
+/// Stroke counterpart of [`crate::dynamic::DynamicRegions`]: the pieces of the strokes between
+/// intersections, each with a style that follows it across edits.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DynamicStrokes {
+    pieces: Vec<PathSeg>,
+    identities: Vec<PieceIdentity>,
+    styles: Vec<PieceStyle>,
+}
+
+impl DynamicStrokes {
+    pub fn new() -> Self {
+        Self {
+            pieces: Vec::new(),
+            identities: Vec::new(),
+            styles: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    pub fn piece(&self, piece: usize) -> PathSeg {
+        self.pieces[piece]
+    }
+
+    pub fn piece_style(&self, piece: usize) -> PieceStyle {
+        self.styles[piece]
+    }
+
+    /// One piece per sub-segment of `intersect_data`, with the parents of the pieces meeting it
+    /// at either end.
+    pub fn build(intersect_data: &IntersectData, tolerances: &Tolerances) -> DynamicStrokes {
+        let dcel = Dcel::build(intersect_data, tolerances);
+        let meeting = |vertex: VertexId, own: usize| {
+            let mut parents = dcel
+                .outgoing(vertex)
+                .iter()
+                .map(|&e| dcel.segment(e))
+                .filter(|&segment| segment != own)
+                .map(|segment| intersect_data.parents[segment])
+                .collect::<Vec<_>>();
+            parents.sort();
+            parents.dedup();
+            parents
+        };
+
+        let mut strokes = DynamicStrokes::new();
+        for (i, &segment) in intersect_data.segments.iter().enumerate() {
+            let (start, end) = dcel.segment_vertices(i);
+            strokes.pieces.push(segment);
+            strokes.identities.push(PieceIdentity {
+                parent: intersect_data.parents[i],
+                interval: intersect_data.intervals[i].clone(),
+                start: meeting(start, i),
+                end: meeting(end, i),
+            });
+            strokes.styles.push(PieceStyle::default());
+        }
+        strokes
+    }
+
+    /// Carries the styles of `prev_dynamic_strokes` over to the pieces they most likely became,
+    /// following `lineage` for segments split, merged or redrawn since.
+    ///
+    /// Unlike regions several pieces can take the style of the same previous piece, so a styled
+    /// piece cut in two by a new stroke keeps its style on both halves.
+    pub fn style(mut self, prev_dynamic_strokes: DynamicStrokes, lineage: &Lineage) -> Self {
+        let prev_identities = prev_dynamic_strokes
+            .identities
+            .iter()
+            .map(|identity| identity.resolve(lineage))
+            .collect::<Vec<_>>();
+        let by_score = |a: &(f64, usize), b: &(f64, usize)| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1));
+
+        for i in 0..self.len() {
+            let best = prev_identities
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| prev_dynamic_strokes.styles[j] != PieceStyle::default())
+                .filter_map(|(j, identities)| {
+                    let score = identities
+                        .iter()
+                        .filter_map(|identity| self.identities[i].score(identity))
+                        .max_by(by_score)?;
+                    Some((j, score))
+                })
+                // Previous pieces are in build order, so ties always resolve to the same piece.
+                .max_by(|a, b| by_score(&a.1, &b.1).then(b.0.cmp(&a.0)));
+
+            if let Some((j, _)) = best {
+                self.styles[i] = prev_dynamic_strokes.styles[j];
+            }
+        }
+        self
+    }
+
+    /// Piece closest to `position`, within the pick radius.
+    pub fn piece_at(&self, position: Point, tolerances: &Tolerances) -> Option<usize> {
+        self.pieces
+            .iter()
+            .enumerate()
+            .map(|(i, piece)| (i, piece.nearest(position, tolerances.accuracy).distance_sq))
+            .filter(|&(_, distance_sq)| distance_sq <= tolerances.pick_radius.powi(2))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    pub fn apply_style(&mut self, style: PieceStyle, position: Point, tolerances: &Tolerances) {
+        if let Some(piece) = self.piece_at(position, tolerances) {
+            self.styles[piece] = style;
+        }
+    }
+
+    /// Strokes every visible piece, falling back to `style` and `color` for what the piece does
+    /// not set.
+    pub fn rasterize(&self, canvas: &mut Canvas, style: &StrokeStyle, color: Color) {
+        for (piece, piece_style) in self.pieces.iter().zip(self.styles.iter()) {
+            if piece_style.hidden {
+                continue;
+            }
+            let mut stroke = style.clone();
+            stroke.width = piece_style.width.unwrap_or(style.width);
+            let path = BezPath::from_path_segments(std::iter::once(*piece));
+            canvas.stroke_path(&path, &stroke, piece_style.color.unwrap_or(color));
+        }
+    }
+
+    #[cfg(feature = "macroquad")]
+    pub fn draw(&self, width: f64, color: Color) {
+        for (piece, piece_style) in self.pieces.iter().zip(self.styles.iter()) {
+            if piece_style.hidden {
+                continue;
+            }
+            let width = piece_style.width.unwrap_or(width) as f32;
+            let color = piece_style.color.unwrap_or(color).into();
+            let steps = 20;
+            for step in 0..steps {
+                let start = piece.eval(step as f64 / steps as f64);
+                let end = piece.eval((step + 1) as f64 / steps as f64);
+                xdraw_line(start, end, width, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::ParamCurve;
+
+    use super::*;
+    use crate::{color::RED, dynamic::intersection, mesh::MMesh};
+
+    #[test]
+    fn style_survives_a_crossing_stroke() {
+        let mut mesh = MMesh::empty();
+        let line = |mesh: &mut MMesh, (x1, y1), (x2, y2)| {
+            let a = mesh.append_point(Point::new(x1, y1));
+            let b = mesh.append_point(Point::new(x2, y2));
+            mesh.append_segment(a, None, None, b);
+        };
+        line(&mut mesh, (0., 50.), (100., 50.));
+        line(&mut mesh, (50., 0.), (50., 100.));
+
+        let tolerances = Tolerances::default();
+        let build =
+            |mesh: &MMesh| DynamicStrokes::build(&intersection(mesh, &tolerances), &tolerances);
+        let mut strokes = build(&mesh);
+        assert_eq!(strokes.len(), 4);
+        let style = PieceStyle::default().with_color(RED).with_width(4.);
+        strokes.apply_style(style, Point::new(30., 51.), &tolerances);
+
+        // Cut the styled piece in two, far from the rest.
+        line(&mut mesh, (25., 0.), (25., 100.));
+        let restyled = build(&mesh).style(strokes, mesh.lineage());
+        assert_eq!(restyled.len(), 7);
+
+        let styled = (0..restyled.len())
+            .filter(|&i| restyled.piece_style(i) == style)
+            .map(|i| restyled.piece(i).eval(0.5))
+            .collect::<Vec<_>>();
+        assert_eq!(styled.len(), 2);
+        assert!(styled.iter().all(|p| p.y == 50. && p.x < 50.));
+    }
+
+    #[test]
+    fn style_stays_off_pieces_sharing_only_their_ends() {
+        let mut mesh = MMesh::empty();
+        let a = mesh.append_point(Point::new(0., 50.));
+        let b = mesh.append_point(Point::new(100., 50.));
+        mesh.append_segment(a, None, None, b);
+        // Crosses the line twice, so both ends of the middle piece meet the same segment as the
+        // inner ends of the outer pieces.
+        let c = mesh.append_point(Point::new(20., 0.));
+        let handle = mesh.append_point(Point::new(50., 200.));
+        let d = mesh.append_point(Point::new(80., 0.));
+        mesh.append_segment(c, Some(handle), None, d);
+
+        let tolerances = Tolerances::default();
+        let build =
+            |mesh: &MMesh| DynamicStrokes::build(&intersection(mesh, &tolerances), &tolerances);
+        let mut strokes = build(&mesh);
+        let style = PieceStyle::default().with_color(RED);
+        strokes.apply_style(style, Point::new(50., 51.), &tolerances);
+
+        let restyled = build(&mesh).style(strokes, mesh.lineage());
+        let styled = (0..restyled.len())
+            .filter(|&i| restyled.piece_style(i) == style)
+            .map(|i| restyled.piece(i).eval(0.5))
+            .collect::<Vec<_>>();
+        assert_eq!(styled, vec![Point::new(50., 50.)]);
+    }
+}
@@ -0,0 +1,248 @@
+use kurbo::{Point, Vec2};
+
+use crate::{
+    color::{BLANK, Color},
+    dynamic::RegionProperties,
+};
+
+/// Colour a gradient takes at `offset`, from 0 at its start to 1 at its end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: Color,
+}
+
+impl GradientStop {
+    pub fn new(offset: f32, color: Color) -> Self {
+        Self { offset, color }
+    }
+}
+
+/// Parallel lines `spacing` apart, running at `angle` radians from the x axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hatch {
+    pub color: Color,
+    /// Colour between the lines, left transparent when [`None`].
+    pub background: Option<Color>,
+    pub angle: f64,
+    pub spacing: f64,
+    pub width: f64,
+    /// Whether a second set of lines crosses the first at a right angle.
+    pub crossed: bool,
+}
+
+impl Hatch {
+    pub fn new(color: Color, angle: f64, spacing: f64, width: f64) -> Self {
+        Self {
+            color,
+            background: None,
+            angle,
+            spacing,
+            width,
+            crossed: false,
+        }
+    }
+
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    pub fn crossed(mut self) -> Self {
+        self.crossed = true;
+        self
+    }
+
+    /// Share of a pixel centred on `offset` covered by the line through the origin, along the
+    /// normal of the lines.
+    fn coverage(&self, offset: f64) -> f64 {
+        let distance = offset.rem_euclid(self.spacing);
+        let distance = distance.min(self.spacing - distance);
+        (self.width / 2. + 0.5 - distance).clamp(0., 1.)
+    }
+}
+
+/// How the inside of a region is painted. Positions are anchored to the region rather than the
+/// canvas, so a fill follows its region when the region moves or changes size.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fill {
+    Solid(Color),
+    /// Blends along the line from `start` to `end`, both given as fractions of the region's
+    /// bounding box, `(0, 0)` being its top left corner.
+    LinearGradient {
+        start: Point,
+        end: Point,
+        stops: Vec<GradientStop>,
+    },
+    /// Blends outwards from the region's centroid, `radius` being a fraction of half the diagonal
+    /// of its bounding box.
+    RadialGradient {
+        radius: f64,
+        stops: Vec<GradientStop>,
+    },
+    /// Lines running through the region's centroid.
+    Hatch(Hatch),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Style {
+    pub fill: Fill,
+    /// Multiplies the alpha of the whole fill.
+    pub opacity: f32,
+}
+
+impl Style {
+    pub fn new(fill: Fill) -> Self {
+        Self { fill, opacity: 1. }
+    }
+
+    pub fn solid(color: Color) -> Self {
+        Self::new(Fill::Solid(color))
+    }
+
+    /// Gradient from `from` on the left of the region to `to` on its right.
+    pub fn horizontal_gradient(from: Color, to: Color) -> Self {
+        Self::new(Fill::LinearGradient {
+            start: Point::new(0., 0.5),
+            end: Point::new(1., 0.5),
+            stops: vec![GradientStop::new(0., from), GradientStop::new(1., to)],
+        })
+    }
+
+    /// Gradient from `center` at the region's centroid to `edge` at its farthest corners.
+    pub fn radial_gradient(center: Color, edge: Color) -> Self {
+        Self::new(Fill::RadialGradient {
+            radius: 1.,
+            stops: vec![GradientStop::new(0., center), GradientStop::new(1., edge)],
+        })
+    }
+
+    pub fn hatch(hatch: Hatch) -> Self {
+        Self::new(Fill::Hatch(hatch))
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Colour of the whole fill when it does not vary across the region.
+    pub fn uniform_color(&self) -> Option<Color> {
+        match self.fill {
+            Fill::Solid(color) => Some(color.with_alpha(color.a * self.opacity)),
+            _ => None,
+        }
+    }
+
+    /// Colour at `point` of a region with the given properties.
+    pub fn color_at(&self, point: Point, region: &RegionProperties) -> Color {
+        let bbox = region.bounding_box;
+        let color = match &self.fill {
+            Fill::Solid(color) => *color,
+            Fill::LinearGradient { start, end, stops } => {
+                let anchor = |p: Point| {
+                    Point::new(bbox.x0 + p.x * bbox.width(), bbox.y0 + p.y * bbox.height())
+                };
+                let (start, end) = (anchor(*start), anchor(*end));
+                let axis = end - start;
+                let t = if axis.hypot2() > 0. {
+                    (point - start).dot(axis) / axis.hypot2()
+                } else {
+                    0.
+                };
+                sample_stops(stops, t)
+            }
+            Fill::RadialGradient { radius, stops } => {
+                let radius = radius * Vec2::new(bbox.width(), bbox.height()).hypot() / 2.;
+                let t = if radius > 0. {
+                    point.distance(region.centroid) / radius
+                } else {
+                    0.
+                };
+                sample_stops(stops, t)
+            }
+            Fill::Hatch(hatch) => {
+                let offset = point - region.centroid;
+                let normal = Vec2::from_angle(hatch.angle + std::f64::consts::FRAC_PI_2);
+                let mut coverage = hatch.coverage(offset.dot(normal));
+                if hatch.crossed {
+                    let across = hatch.coverage(offset.dot(normal.turn_90()));
+                    coverage = coverage.max(across);
+                }
+                let background = hatch.background.unwrap_or(hatch.color.with_alpha(0.));
+                background.lerp(hatch.color, coverage as f32)
+            }
+        };
+        color.with_alpha(color.a * self.opacity)
+    }
+}
+
+/// Colour of a gradient at `t`, holding the first and last colours past either end.
+fn sample_stops(stops: &[GradientStop], t: f64) -> Color {
+    let t = t as f32;
+    let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+        return BLANK;
+    };
+    if t <= first.offset {
+        return first.color;
+    }
+    if t >= last.offset {
+        return last.color;
+    }
+    for pair in stops.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if t <= b.offset {
+            let span = b.offset - a.offset;
+            let local = if span > 0. { (t - a.offset) / span } else { 1. };
+            return a.color.lerp(b.color, local);
+        }
+    }
+    last.color
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Rect;
+
+    use super::*;
+    use crate::{
+        color::{BLUE, RED, WHITE},
+        dynamic::Orientation,
+    };
+
+    fn square() -> RegionProperties {
+        RegionProperties {
+            area: 10000.,
+            centroid: Point::new(150., 150.),
+            perimeter: 400.,
+            bounding_box: Rect::new(100., 100., 200., 200.),
+            orientation: Orientation::Positive,
+        }
+    }
+
+    #[test]
+    fn fills_are_anchored_to_the_region() {
+        let region = square();
+        let gradient = Style::horizontal_gradient(RED, BLUE);
+        assert_eq!(gradient.color_at(Point::new(100., 120.), &region), RED);
+        assert_eq!(gradient.color_at(Point::new(200., 180.), &region), BLUE);
+        assert_eq!(
+            gradient.color_at(Point::new(150., 150.), &region),
+            RED.lerp(BLUE, 0.5)
+        );
+
+        let radial = Style::radial_gradient(WHITE, RED).with_opacity(0.5);
+        assert_eq!(
+            radial.color_at(Point::new(150., 150.), &region),
+            WHITE.with_alpha(0.5)
+        );
+
+        let hatch = Style::hatch(Hatch::new(BLUE, 0., 10., 2.).with_background(WHITE));
+        assert_eq!(hatch.color_at(Point::new(120., 150.), &region), BLUE);
+        assert_eq!(hatch.color_at(Point::new(120., 155.), &region), WHITE);
+    }
+}
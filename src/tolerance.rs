@@ -0,0 +1,93 @@
+use kurbo::DEFAULT_ACCURACY;
+
+/// Geometric tolerances used by intersection, region building and picking.
+///
+/// Distances are in drawing units, so a drawing at a very different scale than the editor
+/// canvas, like an icon or a map, should use [`Tolerances::scaled`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerances {
+    /// Accuracy intersections and overlaps are solved to.
+    pub accuracy: f64,
+    /// Intersections and vertices closer than this are merged into one.
+    pub separation: f64,
+    /// Curve parameter a half-edge is sampled at to find the direction it leaves its origin.
+    pub tangent_sample: f64,
+    /// Distance within which a point is picked.
+    pub pick_radius: f64,
+    /// Gaps up to this wide between a stroke's loose end and the rest of the drawing are bridged
+    /// when building regions, zero leaves every gap open.
+    pub gap: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Self {
+            accuracy: DEFAULT_ACCURACY,
+            separation: 1.5,
+            tangent_sample: 0.02,
+            pick_radius: 3.,
+            gap: 0.,
+        }
+    }
+}
+
+impl Tolerances {
+    /// Scales the distances by `factor`, the parameter sample is left as is.
+    pub fn scaled(self, factor: f64) -> Self {
+        Self {
+            accuracy: self.accuracy * factor,
+            separation: self.separation * factor,
+            tangent_sample: self.tangent_sample,
+            pick_radius: self.pick_radius * factor,
+            gap: self.gap * factor,
+        }
+    }
+
+    pub fn with_accuracy(mut self, accuracy: f64) -> Self {
+        self.accuracy = accuracy;
+        self
+    }
+
+    pub fn with_separation(mut self, separation: f64) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn with_tangent_sample(mut self, tangent_sample: f64) -> Self {
+        self.tangent_sample = tangent_sample;
+        self
+    }
+
+    pub fn with_pick_radius(mut self, pick_radius: f64) -> Self {
+        self.pick_radius = pick_radius;
+        self
+    }
+
+    pub fn with_gap(mut self, gap: f64) -> Self {
+        self.gap = gap;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dcel::Dcel,
+        dynamic::intersection,
+        mesh::{MMesh, fixtures::square_with_diagonal},
+    };
+
+    #[test]
+    fn scaled_tolerances_keep_small_drawings() {
+        let square = |size: f64| square_with_diagonal(size).0;
+        let faces = |mesh: &MMesh, tolerances: &Tolerances| {
+            Dcel::build(&intersection(mesh, tolerances), tolerances).faces_len()
+        };
+
+        let tolerances = Tolerances::default();
+        assert_eq!(faces(&square(100.), &tolerances), 3);
+        assert_ne!(faces(&square(1.), &tolerances), 3);
+        assert_eq!(faces(&square(1.), &tolerances.scaled(0.01)), 3);
+    }
+}
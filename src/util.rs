@@ -1,10 +1,13 @@
-use std::{collections::HashMap, f64};
+use std::collections::HashMap;
 
-use kurbo::{CubicBez, Line, ParamCurve, ParamCurveDeriv, PathSeg, Point, QuadBez};
+use glam::DVec2;
+#[cfg(feature = "macroquad")]
+use kurbo::ParamCurve;
+use kurbo::{CubicBez, Line, PathSeg, Point, QuadBez};
+#[cfg(feature = "macroquad")]
 use macroquad::{
     color::{Color, SKYBLUE},
     input::mouse_position,
-    math::DVec2,
     shapes::{draw_circle, draw_line},
 };
 
@@ -31,16 +34,19 @@ pub fn point_to_gvec2(point: Point) -> glam::Vec2 {
     glam::Vec2::new(point.x as f32, point.y as f32)
 }
 
+#[cfg(feature = "macroquad")]
 pub fn mouse_position_dvec2() -> DVec2 {
     let (x, y) = mouse_position();
     DVec2::new(x as f64, y as f64)
 }
 
+#[cfg(feature = "macroquad")]
 pub fn mouse_position_point() -> Point {
     let (x, y) = mouse_position();
     Point::new(x as f64, y as f64)
 }
 
+#[cfg(feature = "macroquad")]
 pub fn draw_bez(segment: impl ParamCurve) {
     let mut t = 0.;
     let mut last_point: Option<Point> = None;
@@ -95,10 +101,12 @@ pub fn segment_data_to_pathseg(
     points_to_segment(p1, p2, p3, p4)
 }
 
+#[cfg(feature = "macroquad")]
 pub fn xdraw_circle(center: Point, r: f32, color: Color) {
     draw_circle(center.x as f32, center.y as f32, r, color);
 }
 
+#[cfg(feature = "macroquad")]
 pub fn xdraw_line(p1: Point, p2: Point, thickness: f32, color: Color) {
     draw_line(
         p1.x as f32,
@@ -110,19 +118,7 @@ pub fn xdraw_line(p1: Point, p2: Point, thickness: f32, color: Color) {
     );
 }
 
-pub fn pathseg_tangent(segment: PathSeg, t: f64) -> DVec2 {
-    // NOTE: .deriv() method gives inaccurate result when it is 1.
-    let t = t.max(0.98).min(0.02);
-
-    let tangent = match segment {
-        PathSeg::Line(line) => line.deriv().eval(t),
-        PathSeg::Quad(quad_bez) => quad_bez.deriv().eval(t),
-        PathSeg::Cubic(cubic_bez) => cubic_bez.deriv().eval(t),
-    };
-
-    DVec2::new(tangent.x, tangent.y)
-}
-
+#[cfg(feature = "macroquad")]
 pub fn xdraw_segment(segment: PathSeg, color: Color) {
     let mut t = 0.;
     while t < 1. {
@@ -132,3 +128,21 @@ pub fn xdraw_segment(segment: PathSeg, color: Color) {
         t += 0.05
     }
 }
+
+/// Maps every index in `0..len` through `f`, keeping the results in index order. Runs on the rayon
+/// thread pool when the `rayon` feature is enabled, the output is the same either way.
+pub fn map_indices<T, F>(len: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        (0..len).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        (0..len).map(f).collect()
+    }
+}